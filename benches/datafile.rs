@@ -0,0 +1,67 @@
+use chrono::{Local, TimeDelta, Weekday, WeekdaySet};
+use criterion::{Criterion, criterion_group, criterion_main};
+use genee::datafile::{self, DiaryStore, Schedule};
+use std::{path::PathBuf, process::Command};
+
+/// Creates a datafile with random data ending today using the generator binary.
+fn generate_datafile(rows: usize, cols: usize) -> PathBuf {
+    let path =
+        PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("bench_{}x{}.sqlite", rows, cols));
+    let status = Command::new(env!("CARGO_BIN_EXE_generator"))
+        .args(["--file", path.to_str().unwrap()])
+        .args(["--rows", &rows.to_string()])
+        .args(["--cols", &cols.to_string()])
+        .status()
+        .unwrap();
+    assert!(status.success());
+    path
+}
+
+fn bench_statistics(c: &mut Criterion) {
+    let path = generate_datafile(20 * 365, 32);
+    let mut data = datafile::open_datafile(&path, None).unwrap();
+    let today = Local::now().date_naive();
+
+    // Scheduled and excused days are counted separately from the entries
+    let categories = data.get_categories().unwrap();
+    let weekdays = Schedule::Weekdays(WeekdaySet::from_array([
+        Weekday::Mon,
+        Weekday::Wed,
+        Weekday::Fri,
+    ]));
+    data.set_category_schedule(&categories[0].name, &weekdays)
+        .unwrap();
+    let every_n_days = Schedule::EveryNDays {
+        days: 3,
+        anchor: today,
+    };
+    data.set_category_schedule(&categories[1].name, &every_n_days)
+        .unwrap();
+    data.set_excused(
+        None,
+        &(today - TimeDelta::days(400)),
+        &(today - TimeDelta::days(380)),
+        true,
+    )
+    .unwrap();
+
+    for (name, range_size, iters) in [
+        ("monthly", 30, 24),
+        ("yearly", 365, 10),
+        ("five-yearly", 5 * 365, 4),
+    ] {
+        let date_ranges = datafile::get_date_ranges(&today, range_size, iters);
+        c.bench_function(&format!("calculate_data_counts_per_iter {}", name), |b| {
+            b.iter(|| data.calculate_data_counts_per_iter(&date_ranges).unwrap())
+        });
+    }
+    c.bench_function("get_most_frequent_daily_data", |b| {
+        b.iter(|| {
+            data.get_most_frequent_daily_data(&None, &today, Some(10))
+                .unwrap()
+        })
+    });
+}
+
+criterion_group!(benches, bench_statistics);
+criterion_main!(benches);
@@ -1,7 +1,7 @@
 use anyhow::Result;
 use chrono::{Duration, Local, NaiveDate};
 use clap::Parser;
-use genee::datafile;
+use genee::datafile::{self, CategoryId, DiaryStore};
 use rand::prelude::*;
 use std::char;
 use std::path::PathBuf;
@@ -24,30 +24,38 @@ struct Opt {
 fn main() -> Result<()> {
     let opt = Opt::parse();
     let headers = generate_header(opt.cols);
-    datafile::create_new_datafile(&opt.file, &headers)?;
-    let mut data = datafile::open_datafile(&opt.file)?;
+    datafile::create_new_datafile(&opt.file, &headers, None)?;
+    let mut data = datafile::open_datafile(&opt.file, None)?;
     data.update_data_batch(&generate_data(opt.cols, opt.rows))?;
     Ok(())
 }
 
+/// Names the categories like spreadsheet columns, i.e. A to Z, then AA, AB and so on,
+/// so that the names are unique.
 fn generate_header(cols: usize) -> Vec<String> {
-    let mut rng = rand::rng();
+    let letters = Z_IDX - A_IDX + 1;
     let mut header = vec![];
-    for _col in 0..cols {
-        let rand_char = A_IDX + rng.next_u32() % (Z_IDX - A_IDX);
-        header.push(String::from(char::from_u32(rand_char).unwrap()));
+    for col in 0..cols as u32 {
+        let mut name = vec![];
+        let mut remaining = col + 1;
+        while remaining > 0 {
+            remaining -= 1;
+            name.push(char::from_u32(A_IDX + remaining % letters).unwrap());
+            remaining /= letters;
+        }
+        header.push(name.iter().rev().collect());
     }
     header
 }
 
-fn generate_data(cols: usize, rows: usize) -> Vec<(NaiveDate, Vec<usize>)> {
+fn generate_data(cols: usize, rows: usize) -> Vec<(NaiveDate, Vec<CategoryId>)> {
     let mut rng = rand::rng();
     let mut data = vec![];
     for row in 0..rows {
         let mut row_data = vec![];
         for col in 1..cols + 1 {
             if rng.random_bool(0.5) {
-                row_data.push(col)
+                row_data.push(CategoryId(col))
             }
         }
         let date =
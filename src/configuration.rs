@@ -1,5 +1,6 @@
 //! Utilities to store settings persistently on the disk.
 use anyhow::Result;
+use chrono::Weekday;
 use directories_next::ProjectDirs;
 use serde::{Deserialize, Serialize};
 use std::{
@@ -9,10 +10,11 @@ use std::{
     path::PathBuf,
 };
 
-use crate::CliOptions;
+use crate::{CliOptions, PeriodAlignment};
 
 pub const DEFAULT_PAST_PERIODS: usize = 2;
 pub const DEFAULT_LIST_MOST_FREQUENT_DAYS: usize = 5;
+pub const DEFAULT_BACKUP_COUNT: usize = 10;
 const QUALIFIER_ID: &str = "xyz";
 const ORG_ID: &str = "safeworlds";
 const APP_ID: &str = "genee";
@@ -28,6 +30,18 @@ pub struct Config {
 
     /// Specifies the number of most frequent daily habit compositions to print
     pub list_most_frequent_days: usize,
+
+    /// Whether the histogram shows rolling or calendar periods.
+    pub period_alignment: PeriodAlignment,
+
+    /// First day of the calendar weeks.
+    pub week_start: Weekday,
+
+    /// Directory of the backups, when not the default one next to the data file.
+    pub backup_dir: Option<PathBuf>,
+
+    /// How many backups to keep.
+    pub backup_count: usize,
 }
 
 #[derive(Serialize, Deserialize, Default)]
@@ -35,6 +49,10 @@ struct SerializedConfig {
     datafile_path: Option<PathBuf>,
     past_periods: Option<usize>,
     list_most_frequent_days: Option<usize>,
+    period_alignment: Option<PeriodAlignment>,
+    week_start: Option<Weekday>,
+    backup_dir: Option<PathBuf>,
+    backup_count: Option<usize>,
 }
 
 impl SerializedConfig {
@@ -45,6 +63,10 @@ impl SerializedConfig {
             list_most_frequent_days: self
                 .list_most_frequent_days
                 .unwrap_or(DEFAULT_LIST_MOST_FREQUENT_DAYS),
+            period_alignment: self.period_alignment.unwrap_or_default(),
+            week_start: self.week_start.unwrap_or(Weekday::Mon),
+            backup_dir: self.backup_dir,
+            backup_count: self.backup_count.unwrap_or(DEFAULT_BACKUP_COUNT),
         }
     }
 
@@ -53,6 +75,10 @@ impl SerializedConfig {
             datafile_path: Some(config.datafile_path.clone()),
             past_periods: Some(config.past_periods),
             list_most_frequent_days: Some(config.list_most_frequent_days),
+            period_alignment: Some(config.period_alignment),
+            week_start: Some(config.week_start),
+            backup_dir: config.backup_dir.clone(),
+            backup_count: Some(config.backup_count),
         }
     }
 }
@@ -104,6 +130,10 @@ pub fn save_config_opt(opt: &CliOptions) -> Result<()> {
         list_most_frequent_days: opt
             .list_most_frequent_days
             .unwrap_or(DEFAULT_LIST_MOST_FREQUENT_DAYS),
+        period_alignment: opt.period_alignment.unwrap_or_default(),
+        week_start: opt.week_start.unwrap_or(Weekday::Mon),
+        backup_dir: opt.backup_dir.clone(),
+        backup_count: opt.backup_count.unwrap_or(DEFAULT_BACKUP_COUNT),
     };
     save_config(&updated_config)?;
     Ok(())
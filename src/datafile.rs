@@ -1,13 +1,57 @@
 //! Handling of habit databases.
-use anyhow::{Context, Result, bail};
-use chrono::{DateTime, NaiveDate, NaiveTime};
-use std::{ffi::OsString, path::Path};
+use chrono::{Datelike, Days, NaiveDate, TimeDelta, Weekday, WeekdaySet};
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    fmt::Display,
+    fs::{File, OpenOptions},
+    io::{Read, Write},
+    path::{Path, PathBuf},
+    time::Duration,
+};
 
-use rusqlite::{Connection, backup, params};
+use rusqlite::{
+    Connection, OpenFlags, Row, ToSql, params, params_from_iter,
+    types::{FromSql, FromSqlResult, ToSqlOutput, ValueRef},
+};
+use serde::{Deserialize, Serialize};
+
+mod activities;
+mod backup;
+mod doctor;
+mod dump;
+mod encryption;
+mod error;
+mod export;
+mod import;
+mod loop_habits;
+mod memory;
+mod migration;
+mod period;
+mod store;
+
+pub use activities::{ActivityLog, import_activities, read_daylio_export, read_habitica_export};
+pub use backup::{Backup, BackupPolicy, get_default_backup_dir, list_backups, restore_backup};
+pub use doctor::IntegrityReport;
+pub use dump::{CategoryDump, DatafileDump, DayDump, NoteDump};
+pub use encryption::{is_encrypted, rekey};
+pub use error::{Error, Result};
+pub use export::{DelimitedExportOptions, UntrackedDays, export_delimited};
+pub use import::{
+    ConflictPolicy, DEFAULT_TRUTHY_VALUES, DelimitedImportOptions, ImportReport, import_delimited,
+};
+pub use loop_habits::import_loop_backup;
+pub use memory::DiaryDataMemory;
+pub use migration::Migration;
+pub use period::{CalendarPeriod, get_calendar_date_ranges};
+pub use store::DiaryStore;
 
 /// Format of the dates used in the program.
 pub const DATE_FORMAT: &str = "%Y-%m-%d";
 
+/// How long to wait for another connection to release the datafile before failing.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
 /// Result of an update to a `DiaryDataSqlite` instance.
 pub enum SuccessfulUpdate {
     /// The new date was not present in the instance, but it was added.
@@ -17,6 +61,171 @@ pub enum SuccessfulUpdate {
     ReplacedExisting,
 }
 
+/// Identifier of a category, which does not change when the category is renamed or reordered.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(transparent)]
+pub struct CategoryId(pub usize);
+
+impl Display for CategoryId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl ToSql for CategoryId {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        self.0.to_sql()
+    }
+}
+
+impl FromSql for CategoryId {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        usize::column_result(value).map(CategoryId)
+    }
+}
+
+/// Status of a habit entry.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntryStatus {
+    /// The habit was done.
+    #[default]
+    Done,
+
+    /// The habit was partially done. It counts as half an occurrence in the statistics.
+    Partial,
+
+    /// The habit was deliberately skipped. It does not count as an occurrence, but it is not missed either.
+    Skipped,
+}
+
+impl EntryStatus {
+    fn to_db(self) -> usize {
+        match self {
+            EntryStatus::Done => 0,
+            EntryStatus::Partial => 1,
+            EntryStatus::Skipped => 2,
+        }
+    }
+
+    fn from_db(value: usize) -> Self {
+        match value {
+            1 => EntryStatus::Partial,
+            2 => EntryStatus::Skipped,
+            _ => EntryStatus::Done,
+        }
+    }
+}
+
+/// Aggregated habit data of a single category over a period.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CategoryStats {
+    /// Number of days the category occurred, either done or partially done.
+    pub count: usize,
+
+    /// Number of days the category was partially done.
+    pub partial_count: usize,
+
+    /// Number of days the category was deliberately skipped.
+    pub skipped_count: usize,
+
+    /// Sum of the numeric values recorded for the category.
+    pub sum: f64,
+
+    /// Number of days with a numeric value recorded for the category.
+    pub value_count: usize,
+
+    /// Number of days in the period the category was scheduled on, excluding the excused days.
+    pub scheduled_days: usize,
+
+    /// Number of days in the period the category was excused on.
+    pub excused_days: usize,
+}
+
+impl CategoryStats {
+    /// Number of occurrences, where the partially done days count as half.
+    pub fn weighted_count(&self) -> f64 {
+        self.count as f64 - self.partial_count as f64 / 2.0
+    }
+
+    /// Average of the recorded numeric values, if there is any.
+    pub fn average(&self) -> Option<f64> {
+        if self.value_count == 0 {
+            None
+        } else {
+            Some(self.sum / self.value_count as f64)
+        }
+    }
+}
+
+/// Direction of a category target.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TargetKind {
+    /// The category should occur at least the specified number of times.
+    AtLeast,
+
+    /// The category should occur at most the specified number of times.
+    AtMost,
+}
+
+/// Frequency target of a category, e.g. "at least 4 times per 7 days".
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct Target {
+    /// Direction of the target.
+    pub kind: TargetKind,
+
+    /// Number of occurrences.
+    pub count: usize,
+
+    /// Length of the period in days the number of occurrences refers to.
+    pub days: usize,
+}
+
+impl TargetKind {
+    fn to_db(self) -> &'static str {
+        match self {
+            TargetKind::AtLeast => "at_least",
+            TargetKind::AtMost => "at_most",
+        }
+    }
+}
+
+impl Target {
+    /// Returns the target count proportionally scaled to a period with `scheduled_days` days
+    /// the category is scheduled on. The target refers to the days of its own period
+    /// the category is scheduled on, e.g. 3 days of a week for a Mon/Wed/Fri schedule.
+    pub fn scaled_count(&self, schedule: &Schedule, scheduled_days: usize) -> f64 {
+        let target_days = self.days as f64 * schedule.scheduled_fraction();
+        if target_days == 0.0 {
+            return 0.0;
+        }
+        self.count as f64 * scheduled_days as f64 / target_days
+    }
+
+    /// Returns whether `count` occurrences in a period with `scheduled_days` scheduled days
+    /// meet the target.
+    pub fn is_met(&self, count: f64, schedule: &Schedule, scheduled_days: usize) -> bool {
+        let target_count = self.scaled_count(schedule, scheduled_days);
+        match self.kind {
+            TargetKind::AtLeast => count >= target_count,
+            TargetKind::AtMost => count <= target_count,
+        }
+    }
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self.kind {
+            TargetKind::AtLeast => "at least",
+            TargetKind::AtMost => "at most",
+        };
+        write!(f, "{} {} per {} days", kind, self.count, self.days)
+    }
+}
+
 /// Result from the call to `add_category`
 #[derive(Debug, PartialEq)]
 pub enum AddCategoryResult {
@@ -30,6 +239,292 @@ pub enum AddCategoryResult {
     AlreadyPresent,
 }
 
+/// Result from calls that change the properties of an existing category
+#[derive(Debug, PartialEq)]
+pub enum UpdateCategoryResult {
+    /// The category was updated
+    Updated,
+
+    /// The specified category does not exist
+    NonExistingCategory,
+}
+
+/// Result from the call to `rename_category`
+#[derive(Debug, PartialEq)]
+pub enum RenameCategoryResult {
+    /// The category was renamed
+    Renamed,
+
+    /// Another category already has the new name
+    NameTaken,
+
+    /// The new name is empty
+    InvalidName,
+
+    /// The specified category does not exist
+    NonExistingCategory,
+}
+
+/// Result from the call to `merge_category`
+#[derive(Debug, PartialEq)]
+pub enum MergeCategoryResult {
+    /// The entries were moved to the target category and the source category was deleted
+    Merged,
+
+    /// The source and the target are the same category
+    SameCategory,
+
+    /// The specified source category does not exist
+    NonExistingSource,
+
+    /// The specified target category does not exist
+    NonExistingTarget,
+}
+
+/// Descriptive properties of a category used for displaying it.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CategoryDetails {
+    /// Long, human readable name of the category.
+    pub display_name: Option<String>,
+
+    /// Free-text description of the category.
+    pub description: Option<String>,
+
+    /// Emoji or symbol representing the category.
+    pub symbol: Option<String>,
+
+    /// Colour of the category, either a colour name or a hexadecimal RGB value like `#ff8800`.
+    pub color: Option<String>,
+}
+
+/// Whether more or fewer occurrences of a category are desirable.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Polarity {
+    /// Habit to build, more occurrences are better.
+    #[default]
+    Build,
+
+    /// Habit to avoid, fewer occurrences are better.
+    Avoid,
+}
+
+/// Days on which a category is expected to occur.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Schedule {
+    /// Scheduled on the specified days of the week.
+    Weekdays(#[serde(with = "weekday_set")] WeekdaySet),
+
+    /// Scheduled on every `days`-th day, counted from `anchor`.
+    EveryNDays { days: usize, anchor: NaiveDate },
+}
+
+impl Schedule {
+    /// Returns whether the category is scheduled on the specified date.
+    pub fn is_scheduled(&self, date: &NaiveDate) -> bool {
+        match self {
+            Schedule::Weekdays(weekdays) => weekdays.contains(date.weekday()),
+            Schedule::EveryNDays { days, anchor } => {
+                (*date - *anchor).num_days().rem_euclid(*days as i64) == 0
+            }
+        }
+    }
+
+    /// Returns the fraction of the days the category is scheduled on.
+    pub fn scheduled_fraction(&self) -> f64 {
+        match self {
+            Schedule::Weekdays(weekdays) => weekdays.len() as f64 / 7.0,
+            Schedule::EveryNDays { days, .. } => 1.0 / *days as f64,
+        }
+    }
+
+    /// Returns the number of scheduled days between the two dates (inclusive).
+    pub fn count_scheduled_days(&self, from: &NaiveDate, until: &NaiveDate) -> usize {
+        if until < from {
+            return 0;
+        }
+        match self {
+            Schedule::Weekdays(weekdays) => {
+                let days = (*until - *from).num_days() + 1;
+                // Every full week contains each weekday once, only the remaining days are checked
+                let remaining = from
+                    .iter_days()
+                    .take((days % 7) as usize)
+                    .filter(|date| weekdays.contains(date.weekday()))
+                    .count();
+                (days / 7) as usize * weekdays.len() as usize + remaining
+            }
+            Schedule::EveryNDays { days, anchor } => {
+                let days = *days as i64;
+                let scheduled_until = (*until - *anchor).num_days().div_euclid(days);
+                let scheduled_before = ((*from - *anchor).num_days() - 1).div_euclid(days);
+                (scheduled_until - scheduled_before) as usize
+            }
+        }
+    }
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule::Weekdays(WeekdaySet::ALL)
+    }
+}
+
+impl Display for Schedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Schedule::Weekdays(WeekdaySet::ALL) => write!(f, "daily"),
+            Schedule::Weekdays(weekdays) => {
+                let names: Vec<String> = weekdays
+                    .iter(Weekday::Mon)
+                    .map(|weekday| weekday.to_string().to_lowercase())
+                    .collect();
+                write!(f, "{}", names.join(","))
+            }
+            Schedule::EveryNDays { days, anchor } => {
+                write!(f, "every {} days from {}", days, anchor)
+            }
+        }
+    }
+}
+
+/// Serialization of the weekdays of a schedule as a list like `["Mon", "Wed"]`.
+mod weekday_set {
+    use chrono::{Weekday, WeekdaySet};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        weekdays: &WeekdaySet,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let weekdays: Vec<Weekday> = weekdays.iter(Weekday::Mon).collect();
+        weekdays.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<WeekdaySet, D::Error> {
+        Ok(Vec::<Weekday>::deserialize(deserializer)?
+            .into_iter()
+            .collect())
+    }
+}
+
+/// Date range (inclusive) when the habits are excused, e.g. because of a vacation or an illness.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ExcusedRange {
+    /// Category excused in the range, or `None` if the whole diary is excused.
+    pub category_id: Option<CategoryId>,
+
+    /// First excused day.
+    pub from: NaiveDate,
+
+    /// Last excused day.
+    pub until: NaiveDate,
+}
+
+impl ExcusedRange {
+    /// Returns whether the date falls into the range.
+    pub fn contains(&self, date: &NaiveDate) -> bool {
+        self.from <= *date && *date <= self.until
+    }
+
+    /// Returns whether the category is excused on the date by this range.
+    pub fn covers(&self, date: &NaiveDate, category_id: CategoryId) -> bool {
+        self.contains(date) && self.category_id.is_none_or(|id| id == category_id)
+    }
+}
+
+/// Entry of a category on a tracked day.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DayEntry {
+    /// Identifier of the category.
+    pub category_id: CategoryId,
+
+    /// Whether the habit was done, partially done or skipped.
+    #[serde(default)]
+    pub status: EntryStatus,
+
+    /// Numeric value recorded for the entry, e.g. minutes of practice.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<f64>,
+}
+
+/// All data recorded for a single day.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DayRecord {
+    /// Date of the day.
+    pub date: NaiveDate,
+
+    /// Entries of the categories including the hidden ones, ordered by category ID,
+    /// or `None` if the day was not tracked.
+    pub entries: Option<Vec<DayEntry>>,
+
+    /// Note attached to the day.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+
+    /// Whether the whole diary is excused on the day.
+    #[serde(default)]
+    pub excused: bool,
+
+    /// Identifiers of the categories excused on the day separately.
+    #[serde(default)]
+    pub excused_categories: Vec<CategoryId>,
+}
+
+impl DayRecord {
+    /// Creates the record of an untracked day without excuses.
+    pub fn untracked(date: NaiveDate) -> Self {
+        DayRecord {
+            date,
+            entries: None,
+            note: None,
+            excused: false,
+            excused_categories: vec![],
+        }
+    }
+
+    /// Returns the entry of the category, if it has one on this day.
+    pub fn get_entry(&self, category_id: CategoryId) -> Option<&DayEntry> {
+        self.entries
+            .as_ref()?
+            .iter()
+            .find(|entry| entry.category_id == category_id)
+    }
+
+    /// Returns whether the category is excused on this day, either separately or with the whole diary.
+    pub fn is_excused(&self, category_id: CategoryId) -> bool {
+        self.excused || self.excused_categories.contains(&category_id)
+    }
+}
+
+/// A category of the diary, including the hidden ones.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Category {
+    /// Identifier of the category.
+    pub id: CategoryId,
+
+    /// Name (abbreviation) of the category.
+    pub name: String,
+
+    /// Whether the category is hidden.
+    #[serde(default)]
+    pub hidden: bool,
+
+    /// Whether the category is a habit to build or to avoid.
+    #[serde(default)]
+    pub polarity: Polarity,
+
+    /// Days on which the category is expected to occur.
+    #[serde(default)]
+    pub schedule: Schedule,
+
+    /// Descriptive properties of the category.
+    #[serde(default)]
+    pub details: CategoryDetails,
+}
+
 /// Result from the call to `hide_category`
 #[derive(Debug, PartialEq)]
 pub enum HideCategoryResult {
@@ -45,16 +540,12 @@ pub enum HideCategoryResult {
 
 pub struct DiaryDataSqlite {
     connection: Connection,
-}
 
-const CURRENT_DB_VERSION: usize = 1;
+    /// Advisory lock of the datafile, held while it is open for writing.
+    _lock: Option<File>,
 
-fn insert_version_to_db(conn: &Connection) -> Result<()> {
-    conn.execute(
-        "INSERT INTO Info (info_name, info_value) VALUES (\"version\", ?1)",
-        params![CURRENT_DB_VERSION],
-    )?;
-    Ok(())
+    /// Passphrase of the datafile if it is encrypted, the backups are encrypted with it as well.
+    key: Option<String>,
 }
 
 fn initialize_sqlite_database(conn: &Connection, headers: &[String]) -> Result<()> {
@@ -71,214 +562,133 @@ fn initialize_sqlite_database(conn: &Connection, headers: &[String]) -> Result<(
             category_id INTEGER PRIMARY KEY AUTOINCREMENT,
             name TEXT NOT NULL,
             created_at INTEGER NOT NULL,
-            hidden INTEGER NOT NULL
+            hidden INTEGER NOT NULL,
+            target_kind TEXT,
+            target_count INTEGER,
+            target_days INTEGER,
+            sort_order INTEGER NOT NULL DEFAULT 0,
+            display_name TEXT,
+            description TEXT,
+            symbol TEXT,
+            color TEXT,
+            avoid INTEGER NOT NULL DEFAULT 0,
+            schedule_weekdays INTEGER NOT NULL DEFAULT 127,
+            schedule_every INTEGER,
+            schedule_anchor INTEGER
         );
         DROP TABLE IF EXISTS DateEntry;
         CREATE TABLE DateEntry(
-            date DATE PRIMARY KEY,
+            date INTEGER PRIMARY KEY,
             created_at INTEGER NOT NULL
         );
         DROP TABLE IF EXISTS EntryToCategories;
         CREATE TABLE EntryToCategories(
             date INTEGER NOT NULL REFERENCES DateEntry(date) ON DELETE CASCADE,
             category_id INTEGER NOT NULL REFERENCES Category(category_id) ON DELETE CASCADE,
+            value REAL,
+            status INTEGER NOT NULL DEFAULT 0,
             PRIMARY KEY(category_id, date)
         );
+        CREATE INDEX EntryToCategoriesDate ON EntryToCategories(date);
+        DROP TABLE IF EXISTS DateNote;
+        CREATE TABLE DateNote(
+            date INTEGER PRIMARY KEY,
+            note TEXT NOT NULL,
+            created_at INTEGER NOT NULL
+        );
+        DROP TABLE IF EXISTS ExcusedRange;
+        CREATE TABLE ExcusedRange(
+            category_id INTEGER REFERENCES Category(category_id) ON DELETE CASCADE,
+            from_date INTEGER NOT NULL,
+            until_date INTEGER NOT NULL
+        );
         COMMIT;",
     )?;
-    insert_version_to_db(conn)?;
+    migration::set_version(conn, migration::latest_version())?;
     let now = chrono::Local::now().timestamp();
-    for header in headers {
+    for (sort_order, header) in headers.iter().enumerate() {
         conn.execute(
-            "INSERT INTO Category (name, created_at, hidden) VALUES (?1, ?2, 0)",
-            params![header, now],
+            "INSERT INTO Category (name, created_at, hidden, sort_order) VALUES (?1, ?2, 0, ?3)",
+            params![header, now, sort_order],
         )?;
     }
     Ok(())
 }
 
-pub fn create_new_sqlite(path: &Path, headers: &[String]) -> Result<()> {
-    let conn = Connection::open(path).context("Could not open/create SQLite database")?;
+pub fn create_new_sqlite(path: &Path, headers: &[String], key: Option<&str>) -> Result<()> {
+    let _lock = lock_datafile(path)?;
+    let conn = encryption::connect(path, OpenFlags::default(), key)?;
     initialize_sqlite_database(&conn, headers)?;
     Ok(())
 }
 
-fn open_sqlite_database(connection: Connection) -> Result<DiaryDataSqlite> {
-    let data = DiaryDataSqlite { connection };
-    let db_version = data.get_db_version()?;
-    if db_version < CURRENT_DB_VERSION {
-        println!(
-            "Detected an SQLite datafile of version {}. Commencing update...",
-            db_version
-        );
-        data.update_db()?;
-    }
-    Ok(data)
+fn open_sqlite_database(mut connection: Connection) -> Result<DiaryDataSqlite> {
+    connection.execute_batch("PRAGMA foreign_keys=ON;")?;
+    migration::run(&mut connection)?;
+    Ok(DiaryDataSqlite {
+        connection,
+        _lock: None,
+        key: None,
+    })
 }
 
-fn date_to_timestamp(date: &NaiveDate) -> i64 {
-    date.and_time(NaiveTime::default()).and_utc().timestamp()
+/// Parses a list of category identifiers concatenated by `group_concat`.
+fn parse_id_list(list: &str) -> Result<Vec<CategoryId>> {
+    list.split(';')
+        .map(|id| {
+            id.parse()
+                .map(CategoryId)
+                .map_err(|_| Error::CorruptData(format!("invalid category identifier {}", id)))
+        })
+        .collect()
 }
 
-impl DiaryDataSqlite {
-    pub fn into_any(self) -> Box<dyn std::any::Any> {
-        Box::new(self)
-    }
-
-    pub fn calculate_data_counts_per_iter(
-        &self,
-        date_ranges: &[(NaiveDate, NaiveDate)],
-    ) -> Result<Vec<Vec<usize>>> {
-        let category_ids = self.get_visible_category_ids()?;
-        let mut result = vec![];
-        for (from, to) in date_ranges {
-            result.push(self.calculate_data_counts(from, to, &category_ids)?);
-        }
-        Ok(result)
-    }
-
-    pub fn update_data(&mut self, date: &NaiveDate, new_row: &[usize]) -> Result<SuccessfulUpdate> {
-        self.update_data_internal(&[(*date, new_row.to_vec())])
-    }
-
-    pub fn update_data_batch(&mut self, new_items: &[(NaiveDate, Vec<usize>)]) -> Result<()> {
-        self.update_data_internal(new_items)?;
-        Ok(())
-    }
-
-    pub fn get_missing_dates(
-        &self,
-        from: &Option<NaiveDate>,
-        until: &NaiveDate,
-    ) -> Result<Vec<NaiveDate>> {
-        if self.is_empty()? {
-            return Ok(vec![]);
-        }
-
-        // If no from, select the earliest date in the database
-        let from = match from {
-            Some(date) => *date,
-            None => {
-                let mut statement = self.connection.prepare("SELECT MIN(date) FROM DateEntry")?;
-                let min_date = statement.query_row([], |row| row.get(0))?;
-                DateTime::from_timestamp(min_date, 0).unwrap().date_naive()
-            }
-        };
-
-        let mut statement = self
-            .connection
-            .prepare("SELECT date FROM DateEntry WHERE date>=?1 AND date<=?2")?;
-        let from_timestamp = from.and_time(NaiveTime::default()).and_utc().timestamp();
-        let until_timestamp = until.and_time(NaiveTime::default()).and_utc().timestamp();
-        let rows = statement.query_map([from_timestamp, until_timestamp], |row| row.get(0))?;
-        let mut missing_dates = vec![];
-
-        let mut current_date = from;
-        for date_val in rows {
-            let next_present_day = DateTime::from_timestamp(date_val?, 0).unwrap().date_naive();
-            while current_date <= *until {
-                let last_date = current_date;
-                current_date += chrono::Duration::try_days(1).unwrap();
-                if next_present_day == last_date {
-                    break;
-                }
-                missing_dates.push(last_date);
-            }
-        }
-        while current_date <= *until {
-            missing_dates.push(current_date);
-            current_date += chrono::Duration::try_days(1).unwrap();
-        }
-
-        Ok(missing_dates)
-    }
+/// Converts a calendar date to its stored representation, the number of days since 1970-01-01.
+fn date_to_day(date: &NaiveDate) -> i64 {
+    (*date - NaiveDate::default()).num_days()
+}
 
-    pub fn get_header(&self) -> Result<Vec<(String, usize)>> {
-        let mut statement = self.connection.prepare(
-            "SELECT name, category_id FROM Category WHERE hidden=0 ORDER BY category_id",
-        )?;
-        let rows = statement.query_map([], |row| {
-            Ok((row.get::<usize, String>(0)?, row.get::<usize, usize>(1)?))
-        })?;
-        let mut header = vec![];
-        for row in rows {
-            header.push(row?);
-        }
-        Ok(header)
-    }
+/// Converts a stored day number back to a calendar date, if it is in the supported range.
+fn day_to_date(day: i64) -> Option<NaiveDate> {
+    NaiveDate::default().checked_add_signed(TimeDelta::try_days(day)?)
+}
 
-    pub fn get_row(&self, date: &NaiveDate) -> Result<Option<Vec<usize>>> {
-        Ok(self.get_rows(date, date)?.pop().unwrap())
-    }
+/// Reads a date stored as a day number from a result row.
+fn get_date(row: &Row, idx: usize) -> rusqlite::Result<NaiveDate> {
+    let day = row.get(idx)?;
+    day_to_date(day).ok_or(rusqlite::Error::IntegralValueOutOfRange(idx, day))
+}
 
-    pub fn get_rows(&self, from: &NaiveDate, until: &NaiveDate) -> Result<Vec<Option<Vec<usize>>>> {
-        let mut statement = self.connection.prepare(
-            "SELECT date, group_concat(coalesce(category_id, 'EMPTY'), ';') FROM DateEntry
-                LEFT JOIN EntryToCategories USING(date)
-                WHERE date>=?1 AND date<=?2
-                    AND (category_id ISNULL
-                        OR 0=(SELECT hidden FROM Category WHERE EntryToCategories.category_id=Category.category_id))
-                GROUP BY date
-                ORDER BY date DESC")?;
-
-        let mut rows =
-            statement.query(params![date_to_timestamp(from), date_to_timestamp(until)])?;
-        let mut results = vec![];
-        let mut current_date = *until;
-        while current_date >= *from {
-            if let Some(row) = rows.next()? {
-                let timestamp_s: i64 = row.get(0)?;
-                let date = DateTime::from_timestamp(timestamp_s, 0)
-                    .unwrap()
-                    .date_naive();
-
-                while date < current_date {
-                    results.push(None);
-                    current_date -= chrono::Duration::try_days(1).unwrap();
-                }
-                let row_data: String = row.get(1)?;
-                if row_data == "EMPTY" {
-                    results.push(Some(vec![]));
-                } else {
-                    let row_data_parsed = row_data
-                        .split(';')
-                        .map(|id| id.parse::<usize>().unwrap())
-                        .collect();
-                    results.push(Some(row_data_parsed));
-                }
-            } else {
-                results.push(None);
-            }
-            current_date -= chrono::Duration::try_days(1).unwrap();
-        }
-        Ok(results)
-    }
+/// Converts a set of weekdays to a bitmask, where bit 0 stands for Monday.
+fn weekdays_to_mask(weekdays: WeekdaySet) -> u8 {
+    weekdays.iter(Weekday::Mon).fold(0, |mask, weekday| {
+        mask | (1 << weekday.num_days_from_monday())
+    })
+}
 
-    pub fn is_empty(&self) -> Result<bool> {
-        let mut statement = self.connection.prepare("SELECT COUNT(*) FROM DateEntry")?;
-        let count: usize = statement.query_row([], |row| row.get(0))?;
-        Ok(count == 0)
+/// Converts a schedule to the weekday mask, the period and the anchor day stored in the datafile.
+fn schedule_to_db(schedule: &Schedule) -> (u8, Option<usize>, Option<i64>) {
+    match schedule {
+        Schedule::Weekdays(weekdays) => (weekdays_to_mask(*weekdays), None, None),
+        Schedule::EveryNDays { days, anchor } => (
+            weekdays_to_mask(WeekdaySet::ALL),
+            Some(*days),
+            Some(date_to_day(anchor)),
+        ),
     }
+}
 
-    pub fn get_date_range(&self) -> Result<(NaiveDate, NaiveDate)> {
-        if self.is_empty()? {
-            bail!("Cannot get date range, datafile is empty")
+fn mask_to_weekdays(mask: u8) -> WeekdaySet {
+    let mut weekdays = WeekdaySet::EMPTY;
+    for weekday in WeekdaySet::ALL.iter(Weekday::Mon) {
+        if mask & (1 << weekday.num_days_from_monday()) != 0 {
+            weekdays.insert(weekday);
         }
-        let mut statement = self
-            .connection
-            .prepare("SELECT MIN(date), MAX(date) FROM DateEntry")?;
-        let mut rows = statement.query([])?;
-        let row = rows.next()?.unwrap();
-        let min_date = DateTime::from_timestamp(row.get(0)?, 0)
-            .unwrap()
-            .date_naive();
-        let max_date = DateTime::from_timestamp(row.get(1)?, 0)
-            .unwrap()
-            .date_naive();
-
-        Ok((min_date, max_date))
     }
+    weekdays
+}
 
+impl DiaryDataSqlite {
     pub fn add_category(&self, name: &str) -> Result<AddCategoryResult> {
         let mut statement = self
             .connection
@@ -299,9 +709,10 @@ impl DiaryDataSqlite {
                 Ok(AddCategoryResult::AlreadyPresent)
             }
         } else {
-            let mut statement = self
-                .connection
-                .prepare("INSERT INTO Category (name, created_at, hidden) VALUES (?1, ?2, 0)")?;
+            let mut statement = self.connection.prepare(
+                "INSERT INTO Category (name, created_at, hidden, sort_order)
+                    VALUES (?1, ?2, 0, (SELECT COALESCE(MAX(sort_order) + 1, 0) FROM Category))",
+            )?;
             let now = chrono::Local::now().timestamp();
             statement.execute(params![name, now])?;
             Ok(AddCategoryResult::AddedNew)
@@ -330,101 +741,219 @@ impl DiaryDataSqlite {
         }
     }
 
-    pub fn get_most_frequent_daily_data(
-        &self,
-        from: &Option<NaiveDate>,
-        until: &NaiveDate,
-        max_count: Option<usize>,
-    ) -> Result<Vec<(Vec<usize>, usize)>> {
-        let from_timestamp = from
-            .and_then(|from_date| Some(date_to_timestamp(&from_date)))
-            .unwrap_or_default();
-        let until_timestamp = date_to_timestamp(until);
-        let max_count = max_count.unwrap_or(usize::MAX);
+    pub fn rename_category(&self, name: &str, new_name: &str) -> Result<RenameCategoryResult> {
+        if new_name.trim().is_empty() {
+            return Ok(RenameCategoryResult::InvalidName);
+        }
+        if self.get_category_id(new_name)?.is_some() {
+            return Ok(RenameCategoryResult::NameTaken);
+        }
+        let updated = self.connection.execute(
+            "UPDATE Category SET name=?2 WHERE name=?1",
+            params![name, new_name],
+        )?;
+        if updated == 0 {
+            Ok(RenameCategoryResult::NonExistingCategory)
+        } else {
+            Ok(RenameCategoryResult::Renamed)
+        }
+    }
 
-        let mut statement = self.connection.prepare(
-        "SELECT concat_categories, COUNT(date) FROM (
-            SELECT date, group_concat(category_id, ';') AS concat_categories FROM EntryToCategories WHERE date>=(?1) AND date<=(?2)
-                AND 0=(SELECT hidden FROM Category WHERE EntryToCategories.category_id=Category.category_id)
-            GROUP BY date
-        ) GROUP BY concat_categories ORDER BY COUNT(date) DESC LIMIT (?3)
-        ")?;
-        let rows =
-            statement.query_map(params![from_timestamp, until_timestamp, max_count], |row| {
-                Ok((
-                    row.get::<usize, String>(0).unwrap(),
-                    row.get::<usize, usize>(1).unwrap(),
-                ))
-            })?;
-        Ok(rows
-            .into_iter()
-            .map(|row| {
-                let (cat_ids, count) = row.unwrap();
-                let cat_ids = cat_ids
-                    .split(';')
-                    .map(|val| val.parse::<usize>().unwrap())
-                    .collect();
-                (cat_ids, count)
-            })
-            .collect())
+    /// Moves the specified categories to the front of the display order, in the specified order.
+    /// The rest of the categories keep their relative order after them.
+    pub fn reorder_categories(&mut self, names: &[String]) -> Result<UpdateCategoryResult> {
+        let mut ordered_ids = vec![];
+        for name in names {
+            match self.get_category_id(name)? {
+                Some(id) => ordered_ids.push(id),
+                None => return Ok(UpdateCategoryResult::NonExistingCategory),
+            }
+        }
+        for category in self.get_categories()? {
+            if !ordered_ids.contains(&category.id) {
+                ordered_ids.push(category.id);
+            }
+        }
+
+        let transaction = self.connection.transaction()?;
+        {
+            let mut statement =
+                transaction.prepare("UPDATE Category SET sort_order=?2 WHERE category_id=?1")?;
+            for (sort_order, id) in ordered_ids.iter().enumerate() {
+                statement.execute(params![id, sort_order])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(UpdateCategoryResult::Updated)
+    }
+
+    /// Moves all entries of the `source` category to the `target` category, then deletes `source`.
+    /// The numeric values of the entries present in both categories on the same day are summed.
+    pub fn merge_category(&mut self, source: &str, target: &str) -> Result<MergeCategoryResult> {
+        let Some(source_id) = self.get_category_id(source)? else {
+            return Ok(MergeCategoryResult::NonExistingSource);
+        };
+        let Some(target_id) = self.get_category_id(target)? else {
+            return Ok(MergeCategoryResult::NonExistingTarget);
+        };
+        if source_id == target_id {
+            return Ok(MergeCategoryResult::SameCategory);
+        }
+
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "INSERT INTO EntryToCategories (date, category_id, value, status)
+                SELECT date, ?2, value, status FROM EntryToCategories WHERE category_id=?1
+                ON CONFLICT(category_id, date)
+                    DO UPDATE SET value=COALESCE(value + excluded.value, value, excluded.value),
+                        status=MIN(status, excluded.status)",
+            params![source_id, target_id],
+        )?;
+        transaction.execute(
+            "DELETE FROM EntryToCategories WHERE category_id=?1",
+            [source_id],
+        )?;
+        transaction.execute("DELETE FROM Category WHERE category_id=?1", [source_id])?;
+        transaction.commit()?;
+        Ok(MergeCategoryResult::Merged)
+    }
+
+    /// Deletes a category together with all of its entries.
+    pub fn delete_category(&mut self, name: &str) -> Result<UpdateCategoryResult> {
+        let Some(category_id) = self.get_category_id(name)? else {
+            return Ok(UpdateCategoryResult::NonExistingCategory);
+        };
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "DELETE FROM EntryToCategories WHERE category_id=?1",
+            [category_id],
+        )?;
+        transaction.execute("DELETE FROM Category WHERE category_id=?1", [category_id])?;
+        transaction.commit()?;
+        Ok(UpdateCategoryResult::Updated)
     }
 
-    fn calculate_data_counts(
+    /// Replaces the descriptive properties of a category.
+    pub fn set_category_details(
         &self,
-        from: &NaiveDate,
-        to: &NaiveDate,
-        category_ids: &[usize],
-    ) -> Result<Vec<usize>> {
-        let mut result = vec![];
-        for &cat_id in category_ids {
-            let mut statement = self.connection.prepare(
-                "SELECT COUNT(*) FROM EntryToCategories WHERE category_id=?1 AND date<=?2 AND date>=?3",
-            )?;
-            let from_timestamp = date_to_timestamp(from);
-            let to_timestamp = date_to_timestamp(to);
-            let count = statement
-                .query_row(params![cat_id, from_timestamp, to_timestamp], |row| {
-                    row.get(0)
-                })?;
-            result.push(count);
+        name: &str,
+        details: &CategoryDetails,
+    ) -> Result<UpdateCategoryResult> {
+        let updated = self.connection.execute(
+            "UPDATE Category SET display_name=?2, description=?3, symbol=?4, color=?5
+                WHERE name=?1",
+            params![
+                name,
+                details.display_name,
+                details.description,
+                details.symbol,
+                details.color
+            ],
+        )?;
+        if updated == 0 {
+            Ok(UpdateCategoryResult::NonExistingCategory)
+        } else {
+            Ok(UpdateCategoryResult::Updated)
+        }
+    }
+
+    pub fn set_category_polarity(
+        &self,
+        name: &str,
+        polarity: Polarity,
+    ) -> Result<UpdateCategoryResult> {
+        let updated = self.connection.execute(
+            "UPDATE Category SET avoid=?2 WHERE name=?1",
+            params![name, polarity == Polarity::Avoid],
+        )?;
+        if updated == 0 {
+            Ok(UpdateCategoryResult::NonExistingCategory)
+        } else {
+            Ok(UpdateCategoryResult::Updated)
+        }
+    }
+
+    pub fn set_category_schedule(
+        &self,
+        name: &str,
+        schedule: &Schedule,
+    ) -> Result<UpdateCategoryResult> {
+        let (weekdays, every, anchor) = schedule_to_db(schedule);
+        let updated = self.connection.execute(
+            "UPDATE Category SET schedule_weekdays=?2, schedule_every=?3, schedule_anchor=?4
+                WHERE name=?1",
+            params![name, weekdays, every, anchor],
+        )?;
+        if updated == 0 {
+            Ok(UpdateCategoryResult::NonExistingCategory)
+        } else {
+            Ok(UpdateCategoryResult::Updated)
+        }
+    }
+
+    /// Sets or clears (when `target` is `None`) the frequency target of a category.
+    pub fn set_target(&self, name: &str, target: Option<Target>) -> Result<UpdateCategoryResult> {
+        let kind = target.map(|target| target.kind.to_db());
+        let updated = self.connection.execute(
+            "UPDATE Category SET target_kind=?2, target_count=?3, target_days=?4 WHERE name=?1",
+            params![
+                name,
+                kind,
+                target.map(|target| target.count),
+                target.map(|target| target.days)
+            ],
+        )?;
+        if updated == 0 {
+            Ok(UpdateCategoryResult::NonExistingCategory)
+        } else {
+            Ok(UpdateCategoryResult::Updated)
         }
-        Ok(result)
     }
 
     fn update_data_internal(
         &mut self,
-        new_items: &[(NaiveDate, Vec<usize>)],
+        new_items: &[(NaiveDate, Vec<CategoryId>)],
     ) -> Result<SuccessfulUpdate> {
-        let mut statement = self.connection.prepare("BEGIN")?;
-        statement.execute([])?;
+        // The transaction is rolled back when an update fails
+        let transaction = self.connection.transaction()?;
         let mut deleted_date_entries = 0;
 
         for (date, new_category_ids) in new_items {
+            let day = date_to_day(date);
+
+            // Keep the numeric values and the statuses of the categories that stay checked
+            let mut statement = transaction.prepare(
+                "SELECT category_id, value, status FROM EntryToCategories WHERE date=?1",
+            )?;
+            let previous_entries = statement
+                .query_map([day], |row| Ok((row.get(0)?, (row.get(1)?, row.get(2)?))))?
+                .collect::<Result<HashMap<CategoryId, (Option<f64>, usize)>, _>>()?;
+
             // Remove entry in DateEntry if exists
-            let mut statement = self
-                .connection
-                .prepare("DELETE FROM DateEntry WHERE date=?1")?;
-            let date_timestamp = date_to_timestamp(date);
-            deleted_date_entries += statement.execute([date_timestamp])?;
+            let mut statement = transaction.prepare("DELETE FROM DateEntry WHERE date=?1")?;
+            deleted_date_entries += statement.execute([day])?;
 
             // Add entry in DateEntry
             let now = chrono::Local::now().timestamp();
-            let mut statement = self
-                .connection
-                .prepare("INSERT INTO DateEntry (date, created_at) VALUES (?1, ?2)")?;
-            statement.execute(params![date_timestamp, now])?;
+            let mut statement =
+                transaction.prepare("INSERT INTO DateEntry (date, created_at) VALUES (?1, ?2)")?;
+            statement.execute(params![day, now])?;
 
             // Add new associations
-            let mut statement = self
-                .connection
-                .prepare("INSERT INTO EntryToCategories (date, category_id) VALUES (?1, ?2)")?;
+            let mut statement = transaction.prepare(
+                "INSERT INTO EntryToCategories (date, category_id, value, status)
+                    VALUES (?1, ?2, ?3, ?4)",
+            )?;
             for id in new_category_ids {
-                statement.execute(params![date_timestamp, id])?;
+                let (value, status) = previous_entries
+                    .get(id)
+                    .copied()
+                    .unwrap_or((None, EntryStatus::Done.to_db()));
+                statement.execute(params![day, id, value, status])?;
             }
         }
 
-        let mut statement = self.connection.prepare("COMMIT")?;
-        statement.execute([])?;
+        transaction.commit()?;
 
         if deleted_date_entries == 0 {
             Ok(SuccessfulUpdate::AddedNew)
@@ -433,75 +962,614 @@ impl DiaryDataSqlite {
         }
     }
 
-    fn get_db_version(&self) -> Result<usize> {
-        if let Ok(mut statement) = self
+    fn get_category_id(&self, name: &str) -> Result<Option<CategoryId>> {
+        let mut statement = self
             .connection
-            .prepare("SELECT info_value FROM Info WHERE info_name=\"version\"")
-        {
-            let version: Result<String, rusqlite::Error> =
-                statement.query_row([], |row| row.get(0));
-            version
-                .map(|str| Ok(str.parse().unwrap_or(0)))
-                .unwrap_or(Ok(0))
+            .prepare("SELECT category_id FROM Category WHERE name=?1")?;
+        let mut rows = statement.query([name])?;
+        if let Some(row) = rows.next()? {
+            Ok(Some(row.get(0)?))
         } else {
-            Ok(0)
+            Ok(None)
         }
     }
+}
+
+impl DiaryStore for DiaryDataSqlite {
+    fn is_read_only(&self) -> bool {
+        self.connection.is_readonly("main").unwrap_or(true)
+    }
+
+    fn calculate_data_counts_per_iter(
+        &self,
+        date_ranges: &[(NaiveDate, NaiveDate)],
+    ) -> Result<Vec<Vec<CategoryStats>>> {
+        let categories = self.get_header()?;
+        let mut result =
+            store::count_scheduled_days(&categories, &self.get_excused_ranges()?, date_ranges);
+        if date_ranges.is_empty() {
+            return Ok(result);
+        }
 
-    fn update_db_to_v1(&self) -> Result<()> {
-        println!("- Updating SQLite datafile to version 1...");
+        // The periods are stored in a temporary table keyed by their index, so that the entries
+        // of all periods are aggregated by a single query walking the primary key in group order
         self.connection.execute_batch(
-            "BEGIN;
-            DROP TABLE IF EXISTS Info;
-            CREATE TABLE Info(
-                info_id INTEGER PRIMARY KEY AUTOINCREMENT,
-                info_name TEXT UNIQUE NOT NULL,
-                info_value TEXT NOT NULL
+            "CREATE TEMP TABLE IF NOT EXISTS StatsPeriod(
+                period_idx INTEGER PRIMARY KEY,
+                until_day INTEGER NOT NULL,
+                from_day INTEGER NOT NULL
             );
-            ALTER TABLE Category ADD COLUMN hidden INTEGER NOT NULL DEFAULT 0;
-            COMMIT;",
+            DELETE FROM temp.StatsPeriod;",
+        )?;
+        let periods = vec!["(?, ?, ?)"; date_ranges.len()].join(", ");
+        self.connection.execute(
+            &format!("INSERT INTO temp.StatsPeriod VALUES {periods}"),
+            params_from_iter(
+                date_ranges
+                    .iter()
+                    .enumerate()
+                    .flat_map(|(idx, (until, from))| {
+                        [idx as i64, date_to_day(until), date_to_day(from)]
+                    }),
+            ),
+        )?;
+        // Only the entries on the scheduled days are counted. Day number 0 (1970-01-01) is a Thursday.
+        let mut statement = self.connection.prepare(
+            "SELECT period_idx, category_id,
+                    TOTAL(status<>2), TOTAL(status=1), TOTAL(status=2),
+                    TOTAL(value) FILTER (WHERE status<>2), COUNT(value) FILTER (WHERE status<>2)
+                FROM Category
+                CROSS JOIN temp.StatsPeriod
+                CROSS JOIN EntryToCategories USING(category_id)
+                WHERE hidden=0 AND date<=until_day AND date>=from_day
+                    AND CASE WHEN schedule_every ISNULL
+                        THEN (schedule_weekdays >> ((((date + 3) % 7) + 7) % 7)) & 1
+                        ELSE (date - schedule_anchor) % schedule_every = 0
+                    END
+                GROUP BY category_id, period_idx",
+        )?;
+        let category_indices: HashMap<CategoryId, usize> = categories
+            .iter()
+            .enumerate()
+            .map(|(idx, category)| (category.id, idx))
+            .collect();
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            let Some(category_idx) = category_indices.get(&row.get(1)?) else {
+                continue;
+            };
+            let stats = &mut result[row.get::<usize, usize>(0)?][*category_idx];
+            stats.count = row.get::<usize, f64>(2)? as usize;
+            stats.partial_count = row.get::<usize, f64>(3)? as usize;
+            stats.skipped_count = row.get::<usize, f64>(4)? as usize;
+            stats.sum = row.get(5)?;
+            stats.value_count = row.get(6)?;
+        }
+        Ok(result)
+    }
+
+    fn update_data(
+        &mut self,
+        date: &NaiveDate,
+        new_row: &[CategoryId],
+    ) -> Result<SuccessfulUpdate> {
+        self.update_data_internal(&[(*date, new_row.to_vec())])
+    }
+
+    fn update_data_batch(&mut self, new_items: &[(NaiveDate, Vec<CategoryId>)]) -> Result<()> {
+        self.update_data_internal(new_items)?;
+        Ok(())
+    }
+
+    fn update_entries_batch(&mut self, new_items: &[(NaiveDate, Vec<DayEntry>)]) -> Result<()> {
+        // The transaction is rolled back when an update fails
+        let transaction = self.connection.transaction()?;
+        let now = chrono::Local::now().timestamp();
+        for (date, entries) in new_items {
+            let day = date_to_day(date);
+            transaction.execute("DELETE FROM DateEntry WHERE date=?1", [day])?;
+            transaction.execute(
+                "INSERT INTO DateEntry (date, created_at) VALUES (?1, ?2)",
+                params![day, now],
+            )?;
+            let mut statement = transaction.prepare_cached(
+                "INSERT INTO EntryToCategories (date, category_id, value, status)
+                    VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for entry in entries {
+                statement.execute(params![
+                    day,
+                    entry.category_id,
+                    entry.value,
+                    entry.status.to_db()
+                ])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn get_values(
+        &self,
+        from: &NaiveDate,
+        until: &NaiveDate,
+    ) -> Result<Vec<(NaiveDate, CategoryId, f64)>> {
+        let mut statement = self.connection.prepare(
+            "SELECT date, category_id, value FROM EntryToCategories
+                WHERE value NOTNULL AND date>=?1 AND date<=?2",
+        )?;
+        let rows = statement.query_map(params![date_to_day(from), date_to_day(until)], |row| {
+            Ok((get_date(row, 0)?, row.get(1)?, row.get::<usize, f64>(2)?))
+        })?;
+        let mut values = vec![];
+        for row in rows {
+            values.push(row?);
+        }
+        Ok(values)
+    }
+
+    fn set_value(
+        &mut self,
+        date: &NaiveDate,
+        category_id: CategoryId,
+        value: Option<f64>,
+    ) -> Result<()> {
+        let day = date_to_day(date);
+        let transaction = self.connection.transaction()?;
+        let now = chrono::Local::now().timestamp();
+        transaction.execute(
+            "INSERT OR IGNORE INTO DateEntry (date, created_at) VALUES (?1, ?2)",
+            params![day, now],
+        )?;
+        transaction.execute(
+            "INSERT INTO EntryToCategories (date, category_id, value) VALUES (?1, ?2, ?3)
+                ON CONFLICT(category_id, date) DO UPDATE SET value=excluded.value",
+            params![day, category_id, value],
+        )?;
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn get_statuses(
+        &self,
+        from: &NaiveDate,
+        until: &NaiveDate,
+    ) -> Result<Vec<(NaiveDate, CategoryId, EntryStatus)>> {
+        let mut statement = self.connection.prepare(
+            "SELECT date, category_id, status FROM EntryToCategories
+                WHERE date>=?1 AND date<=?2 ORDER BY date, category_id",
+        )?;
+        let rows = statement.query_map(params![date_to_day(from), date_to_day(until)], |row| {
+            Ok((
+                get_date(row, 0)?,
+                row.get(1)?,
+                EntryStatus::from_db(row.get(2)?),
+            ))
+        })?;
+        let mut statuses = vec![];
+        for row in rows {
+            statuses.push(row?);
+        }
+        Ok(statuses)
+    }
+
+    fn set_status(
+        &mut self,
+        date: &NaiveDate,
+        category_id: CategoryId,
+        status: Option<EntryStatus>,
+    ) -> Result<()> {
+        let day = date_to_day(date);
+        let transaction = self.connection.transaction()?;
+        let now = chrono::Local::now().timestamp();
+        transaction.execute(
+            "INSERT OR IGNORE INTO DateEntry (date, created_at) VALUES (?1, ?2)",
+            params![day, now],
         )?;
-        insert_version_to_db(&self.connection)?;
-        println!("- Success");
+        match status {
+            Some(status) => transaction.execute(
+                "INSERT INTO EntryToCategories (date, category_id, status) VALUES (?1, ?2, ?3)
+                    ON CONFLICT(category_id, date) DO UPDATE SET status=excluded.status",
+                params![day, category_id, status.to_db()],
+            )?,
+            None => transaction.execute(
+                "DELETE FROM EntryToCategories WHERE date=?1 AND category_id=?2",
+                params![day, category_id],
+            )?,
+        };
+        transaction.commit()?;
         Ok(())
     }
 
-    fn update_db(&self) -> Result<()> {
-        self.update_db_to_v1()?;
+    fn get_note(&self, date: &NaiveDate) -> Result<Option<String>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT note FROM DateNote WHERE date=?1")?;
+        let mut rows = statement.query([date_to_day(date)])?;
+        if let Some(row) = rows.next()? {
+            Ok(Some(row.get(0)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn set_note(&mut self, date: &NaiveDate, note: &str) -> Result<()> {
+        let day = date_to_day(date);
+        if note.is_empty() {
+            self.connection
+                .execute("DELETE FROM DateNote WHERE date=?1", [day])?;
+        } else {
+            let now = chrono::Local::now().timestamp();
+            self.connection.execute(
+                "INSERT INTO DateNote (date, note, created_at) VALUES (?1, ?2, ?3)
+                    ON CONFLICT(date) DO UPDATE SET note=excluded.note, created_at=excluded.created_at",
+                params![day, note, now],
+            )?;
+        }
         Ok(())
     }
 
-    fn get_visible_category_ids(&self) -> Result<Vec<usize>> {
+    fn get_days(&self, from: &NaiveDate, until: &NaiveDate) -> Result<Vec<DayRecord>> {
+        let mut records: Vec<DayRecord> = from
+            .iter_days()
+            .take_while(|date| date <= until)
+            .map(DayRecord::untracked)
+            .collect();
+        let day_range = params![date_to_day(from), date_to_day(until)];
+
+        // The tracked days without any entry have a single row without a category
+        let mut statement = self.connection.prepare(
+            "SELECT date, category_id, status, value FROM DateEntry
+                LEFT JOIN EntryToCategories USING(date)
+                WHERE date>=?1 AND date<=?2
+                ORDER BY date, category_id",
+        )?;
+        let mut rows = statement.query(day_range)?;
+        while let Some(row) = rows.next()? {
+            let record = &mut records[(get_date(row, 0)? - *from).num_days() as usize];
+            let entries = record.entries.get_or_insert_with(Vec::new);
+            if let Some(category_id) = row.get(1)? {
+                entries.push(DayEntry {
+                    category_id,
+                    status: EntryStatus::from_db(row.get(2)?),
+                    value: row.get(3)?,
+                });
+            }
+        }
+
         let mut statement = self
             .connection
-            .prepare("SELECT category_id FROM Category WHERE hidden=0 ORDER BY category_id")?;
-        let rows = statement.query_map([], |row| row.get(0))?;
+            .prepare("SELECT date, note FROM DateNote WHERE date>=?1 AND date<=?2")?;
+        let mut rows = statement.query(day_range)?;
+        while let Some(row) = rows.next()? {
+            records[(get_date(row, 0)? - *from).num_days() as usize].note = Some(row.get(1)?);
+        }
+
+        for range in self.get_excused_ranges()? {
+            for record in records
+                .iter_mut()
+                .filter(|record| range.contains(&record.date))
+            {
+                match range.category_id {
+                    None => record.excused = true,
+                    Some(id) => record.excused_categories.push(id),
+                }
+            }
+        }
+        Ok(records)
+    }
+
+    fn is_empty(&self) -> Result<bool> {
+        let mut statement = self.connection.prepare("SELECT COUNT(*) FROM DateEntry")?;
+        let count: usize = statement.query_row([], |row| row.get(0))?;
+        Ok(count == 0)
+    }
+
+    fn get_date_range(&self) -> Result<(NaiveDate, NaiveDate)> {
+        if self.is_empty()? {
+            return Err(Error::EmptyDiary);
+        }
+        Ok(self
+            .connection
+            .query_row("SELECT MIN(date), MAX(date) FROM DateEntry", [], |row| {
+                Ok((get_date(row, 0)?, get_date(row, 1)?))
+            })?)
+    }
+
+    fn get_categories(&self) -> Result<Vec<Category>> {
+        let mut statement = self.connection.prepare(
+            "SELECT category_id, name, hidden, display_name, description, symbol, color, avoid,
+                    schedule_weekdays, schedule_every, schedule_anchor
+                FROM Category ORDER BY sort_order, category_id",
+        )?;
+        let rows = statement.query_map([], |row| {
+            Ok(Category {
+                id: row.get(0)?,
+                name: row.get(1)?,
+                hidden: 0usize != row.get::<usize, usize>(2)?,
+                polarity: if 0usize != row.get::<usize, usize>(7)? {
+                    Polarity::Avoid
+                } else {
+                    Polarity::Build
+                },
+                schedule: match (row.get(9)?, row.get::<usize, Option<i64>>(10)?) {
+                    (Some(days), Some(anchor)) if day_to_date(anchor).is_some() => {
+                        Schedule::EveryNDays {
+                            days,
+                            anchor: get_date(row, 10)?,
+                        }
+                    }
+                    _ => Schedule::Weekdays(mask_to_weekdays(row.get(8)?)),
+                },
+                details: CategoryDetails {
+                    display_name: row.get(3)?,
+                    description: row.get(4)?,
+                    symbol: row.get(5)?,
+                    color: row.get(6)?,
+                },
+            })
+        })?;
+        let mut categories = vec![];
+        for row in rows {
+            categories.push(row?);
+        }
+        Ok(categories)
+    }
+
+    fn get_excused_ranges(&self) -> Result<Vec<ExcusedRange>> {
+        let mut statement = self.connection.prepare(
+            "SELECT category_id, from_date, until_date FROM ExcusedRange ORDER BY from_date",
+        )?;
+        let rows = statement.query_map([], |row| {
+            Ok(ExcusedRange {
+                category_id: row.get(0)?,
+                from: get_date(row, 1)?,
+                until: get_date(row, 2)?,
+            })
+        })?;
+        let mut ranges = vec![];
+        for range in rows {
+            ranges.push(range?);
+        }
+        Ok(ranges)
+    }
+
+    fn set_excused(
+        &mut self,
+        category_id: Option<CategoryId>,
+        from: &NaiveDate,
+        until: &NaiveDate,
+        excused: bool,
+    ) -> Result<()> {
+        let tx = self.connection.transaction()?;
+        let affected_ranges = {
+            let mut statement = tx.prepare(
+                "DELETE FROM ExcusedRange
+                    WHERE category_id IS ?1 AND from_date<=?2 AND until_date>=?3
+                    RETURNING from_date, until_date",
+            )?;
+            let rows = statement.query_map(
+                params![category_id, date_to_day(until) + 1, date_to_day(from) - 1],
+                |row| Ok((get_date(row, 0)?, get_date(row, 1)?)),
+            )?;
+            let mut ranges = vec![];
+            for range in rows {
+                ranges.push(range?);
+            }
+            ranges
+        };
+        let new_ranges = store::split_excused_ranges(&affected_ranges, from, until, excused);
+        for (range_from, range_until) in new_ranges {
+            tx.execute(
+                "INSERT INTO ExcusedRange (category_id, from_date, until_date) VALUES (?1, ?2, ?3)",
+                params![
+                    category_id,
+                    date_to_day(&range_from),
+                    date_to_day(&range_until)
+                ],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    fn get_days_since_last_occurrence(
+        &self,
+        category_id: CategoryId,
+        until: &NaiveDate,
+    ) -> Result<Option<usize>> {
+        let mut statement = self.connection.prepare(
+            "SELECT MAX(date) FROM EntryToCategories
+                    WHERE category_id=?1 AND date<=?2 AND status<>2",
+        )?;
+        let until_day = date_to_day(until);
+        let last_day: Option<i64> =
+            statement.query_row(params![category_id, until_day], |row| row.get(0))?;
+        Ok(last_day.map(|last_day| (until_day - last_day) as usize))
+    }
+
+    fn get_streak(&self, category_id: CategoryId, until: &NaiveDate) -> Result<usize> {
+        let (polarity, schedule) = store::get_polarity_and_schedule(self, category_id)?;
+        if polarity == Polarity::Avoid {
+            return store::get_days_without_occurrence(self, category_id, until);
+        }
+
+        let mut statement = self.connection.prepare(
+            "SELECT date, status FROM EntryToCategories WHERE category_id=?1 AND date<=?2
+                ORDER BY date DESC",
+        )?;
+        let entries = statement.query_map(params![category_id, date_to_day(until)], |row| {
+            Ok((get_date(row, 0)?, EntryStatus::from_db(row.get(1)?)))
+        })?;
+        store::count_streak(
+            entries.map(|entry| Ok(entry?)),
+            &schedule,
+            &self.get_excused_ranges()?,
+            category_id,
+            until,
+        )
+    }
+
+    fn get_targets(&self) -> Result<HashMap<CategoryId, Target>> {
+        let mut statement = self.connection.prepare(
+            "SELECT category_id, target_kind, target_count, target_days FROM Category
+                WHERE target_kind NOTNULL",
+        )?;
+        let mut rows = statement.query([])?;
+        let mut targets = HashMap::new();
+        while let Some(row) = rows.next()? {
+            let kind = match row.get::<usize, String>(1)?.as_str() {
+                "at_least" => TargetKind::AtLeast,
+                "at_most" => TargetKind::AtMost,
+                other => {
+                    return Err(Error::CorruptData(format!("invalid target kind {}", other)));
+                }
+            };
+            targets.insert(
+                row.get(0)?,
+                Target {
+                    kind,
+                    count: row.get(2)?,
+                    days: row.get(3)?,
+                },
+            );
+        }
+        Ok(targets)
+    }
+
+    fn get_most_frequent_daily_data(
+        &self,
+        from: &Option<NaiveDate>,
+        until: &NaiveDate,
+        max_count: Option<usize>,
+    ) -> Result<Vec<(Vec<CategoryId>, usize)>> {
+        let from_day = from
+            .map(|from_date| date_to_day(&from_date))
+            .unwrap_or(i64::MIN);
+        let until_day = date_to_day(until);
+        // A negative limit means no limit in SQLite
+        let max_count = max_count.map_or(-1, |count| count as i64);
 
-        // Ordered list of all category IDs in the database
-        let mut category_ids: Vec<usize> = vec![];
-        for id in rows {
-            category_ids.push(id?);
+        let mut statement = self.connection.prepare(
+            "SELECT concat_categories, COUNT(date) FROM (
+            SELECT date, group_concat(category_id, ';' ORDER BY category_id) AS concat_categories
+                FROM EntryToCategories JOIN Category USING(category_id)
+                WHERE date>=(?1) AND date<=(?2) AND status<>2 AND hidden=0 AND avoid=0
+            GROUP BY date
+        ) GROUP BY concat_categories ORDER BY COUNT(date) DESC LIMIT (?3)
+        ",
+        )?;
+        let rows = statement.query_map(params![from_day, until_day, max_count], |row| {
+            Ok((row.get::<usize, String>(0)?, row.get::<usize, usize>(1)?))
+        })?;
+        let mut result = vec![];
+        for row in rows {
+            let (cat_ids, count) = row?;
+            result.push((parse_id_list(&cat_ids)?, count));
         }
-        Ok(category_ids)
+        Ok(result)
+    }
+}
+
+/// Opens a datafile and updates it to the latest schema version.
+/// The datafile is backed up before the update.
+/// Fails if the datafile was written by a newer version of the program.
+/// Fails if another running instance has the datafile open for writing.
+/// Encrypted datafiles are opened with the passphrase, which is used for the backups as well.
+pub fn open_datafile(path: &Path, key: Option<&str>) -> Result<DiaryDataSqlite> {
+    let lock = lock_datafile(path)?;
+    let connection = encryption::connect(path, OpenFlags::default(), key)?;
+    connection.busy_timeout(BUSY_TIMEOUT)?;
+    connection.execute_batch("PRAGMA journal_mode=WAL;")?;
+    let version = migration::get_version(&connection)?;
+    let pending = migration::get_pending(version)?;
+    if !pending.is_empty() {
+        let backup_path = get_migration_backup_path(path, version);
+        backup_datafile(&connection, &backup_path, key)?;
     }
+    let mut datafile = open_sqlite_database(connection)?;
+    datafile._lock = Some(lock);
+    datafile.key = key.map(String::from);
+    Ok(datafile)
 }
 
-/// Tries to read data file to memory.
-pub fn open_datafile(path: &Path) -> Result<DiaryDataSqlite> {
-    let connection = Connection::open(path).context("Could not open SQLite database")?;
-    {
-        let mut backup_ext = OsString::from(path.extension().unwrap_or_default());
-        backup_ext.push(".bak");
-        let backup_path = path.with_extension(backup_ext);
-        let mut backup_connection =
-            Connection::open(backup_path).context("Could not open SQLite database for backup")?;
-        let backup = backup::Backup::new(&connection, &mut backup_connection)
-            .context("Could not initiate database backup")?;
-        backup
-            .run_to_completion(10, std::time::Duration::default(), None)
-            .context("Could not perform backup")?;
+/// Opens a datafile without modifying it: it is neither backed up nor locked, and every edit fails.
+/// Fails if the datafile is not at the latest schema version, as it cannot be updated.
+pub fn open_datafile_read_only(path: &Path, key: Option<&str>) -> Result<DiaryDataSqlite> {
+    let connection = encryption::connect(path, OpenFlags::SQLITE_OPEN_READ_ONLY, key)?;
+    connection.busy_timeout(BUSY_TIMEOUT)?;
+    let version = migration::get_version(&connection)?;
+    if !migration::get_pending(version)?.is_empty() {
+        return Err(Error::MigrationRequired {
+            version,
+            latest: migration::latest_version(),
+        });
+    }
+    Ok(DiaryDataSqlite {
+        connection,
+        _lock: None,
+        key: key.map(String::from),
+    })
+}
+
+/// Takes the advisory lock of the datafile, which is released when the returned file is closed.
+/// The lock file next to the datafile records the process identifier of the holder.
+fn lock_datafile(path: &Path) -> Result<File> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(get_backup_path(path, "lock"))
+        .map_err(Error::io("Could not open the lock file of the datafile"))?;
+    match file.try_lock() {
+        Ok(()) => {}
+        Err(std::fs::TryLockError::WouldBlock) => {
+            let mut holder = String::new();
+            let _ = file.read_to_string(&mut holder);
+            let holder = holder.trim();
+            return Err(Error::Locked {
+                holder: (!holder.is_empty()).then(|| String::from(holder)),
+            });
+        }
+        Err(std::fs::TryLockError::Error(error)) => {
+            return Err(Error::io("Could not lock the datafile")(error));
+        }
     }
-    open_sqlite_database(connection)
+    file.set_len(0)
+        .and_then(|_| write!(file, "{}", std::process::id()))
+        .map_err(Error::io("Could not write the lock file of the datafile"))?;
+    Ok(file)
+}
+
+/// Returns the schema version of the datafile and the migrations pending to update it,
+/// without modifying the datafile.
+pub fn get_pending_migrations(
+    path: &Path,
+    key: Option<&str>,
+) -> Result<(usize, &'static [Migration])> {
+    let connection = encryption::connect(path, OpenFlags::SQLITE_OPEN_READ_ONLY, key)?;
+    let version = migration::get_version(&connection)?;
+    Ok((version, migration::get_pending(version)?))
+}
+
+/// Returns the path of the backup made before migrating a datafile of the specified version.
+pub fn get_migration_backup_path(path: &Path, version: usize) -> PathBuf {
+    get_backup_path(path, &format!("v{}.bak", version))
+}
+
+fn get_backup_path(path: &Path, suffix: &str) -> PathBuf {
+    let mut backup_ext = OsString::from(path.extension().unwrap_or_default());
+    backup_ext.push(".");
+    backup_ext.push(suffix);
+    path.with_extension(backup_ext)
+}
+
+fn backup_datafile(connection: &Connection, backup_path: &Path, key: Option<&str>) -> Result<()> {
+    let mut backup_connection = encryption::connect(backup_path, OpenFlags::default(), key)?;
+    let backup = rusqlite::backup::Backup::new(connection, &mut backup_connection)?;
+    backup.run_to_completion(10, std::time::Duration::default(), None)?;
+    Ok(())
 }
 
 /// Calculates the date ranges according to the parameters.
@@ -513,22 +1581,25 @@ pub fn get_date_ranges(
     range_size: usize,
     iters: usize,
 ) -> Vec<(NaiveDate, NaiveDate)> {
+    let range_size = range_size.max(1);
     let start_offsets = (0..range_size * iters).step_by(range_size);
     let end_offsets = (range_size - 1..range_size * (iters + 1)).step_by(range_size);
+    // The ranges reaching beyond the earliest supported date are cut at it
+    let days_before = |offset: usize| {
+        from_date
+            .checked_sub_days(Days::new(offset as u64))
+            .unwrap_or(NaiveDate::MIN)
+    };
     start_offsets
         .zip(end_offsets)
-        .map(|(start, end)| {
-            (
-                *from_date - chrono::Duration::try_days(start as i64).unwrap(),
-                *from_date - chrono::Duration::try_days(end as i64).unwrap(),
-            )
-        })
+        .map(|(start, end)| (days_before(start), days_before(end)))
         .collect()
 }
 
 /// Create a new database on the prescribed path, using the prescribed headers.
-pub fn create_new_datafile(path: &Path, headers: &[String]) -> Result<()> {
-    create_new_sqlite(path, headers)?;
+/// With a passphrase, the new datafile is encrypted.
+pub fn create_new_datafile(path: &Path, headers: &[String], key: Option<&str>) -> Result<()> {
+    create_new_sqlite(path, headers, key)?;
     Ok(())
 }
 
@@ -553,3 +1624,1461 @@ fn test_get_date_ranges() {
         result
     );
 }
+
+#[cfg(test)]
+fn open_test_database(headers: &[&str]) -> DiaryDataSqlite {
+    let connection = Connection::open_in_memory().unwrap();
+    let headers: Vec<String> = headers.iter().map(|name| String::from(*name)).collect();
+    initialize_sqlite_database(&connection, &headers).unwrap();
+    open_sqlite_database(connection).unwrap()
+}
+
+#[cfg(test)]
+fn ids<const N: usize>(ids: [usize; N]) -> Vec<CategoryId> {
+    ids.into_iter().map(CategoryId).collect()
+}
+
+#[test]
+fn test_get_calendar_date_ranges() {
+    let date = |year, month, day| NaiveDate::from_ymd_opt(year, month, day).unwrap();
+    // 2025-03-12 is a Wednesday
+    let ranges =
+        get_calendar_date_ranges(&date(2025, 3, 12), CalendarPeriod::Week, Weekday::Mon, 2);
+    assert_eq!(
+        vec![
+            (date(2025, 3, 12), date(2025, 3, 10)),
+            (date(2025, 3, 9), date(2025, 3, 3))
+        ],
+        ranges
+    );
+    assert_eq!("2025-W11", CalendarPeriod::Week.label(&ranges[0].1));
+    let ranges =
+        get_calendar_date_ranges(&date(2025, 3, 12), CalendarPeriod::Week, Weekday::Sun, 1);
+    assert_eq!(vec![(date(2025, 3, 12), date(2025, 3, 9))], ranges);
+    assert_eq!("2025-W11", CalendarPeriod::Week.label(&ranges[0].1));
+    // The first ISO week of 2025 starts in 2024
+    assert_eq!("2025-W01", CalendarPeriod::Week.label(&date(2024, 12, 30)));
+
+    let ranges =
+        get_calendar_date_ranges(&date(2024, 3, 31), CalendarPeriod::Month, Weekday::Mon, 3);
+    assert_eq!(
+        vec![
+            (date(2024, 3, 31), date(2024, 3, 1)),
+            (date(2024, 2, 29), date(2024, 2, 1)),
+            (date(2024, 1, 31), date(2024, 1, 1))
+        ],
+        ranges
+    );
+    assert_eq!("2024-02", CalendarPeriod::Month.label(&ranges[1].1));
+
+    let ranges =
+        get_calendar_date_ranges(&date(2025, 1, 5), CalendarPeriod::Quarter, Weekday::Mon, 2);
+    assert_eq!(
+        vec![
+            (date(2025, 1, 5), date(2025, 1, 1)),
+            (date(2024, 12, 31), date(2024, 10, 1))
+        ],
+        ranges
+    );
+    assert_eq!("2024-Q4", CalendarPeriod::Quarter.label(&ranges[1].1));
+    assert_eq!(
+        vec![(date(2025, 6, 1), date(2025, 1, 1))],
+        get_calendar_date_ranges(&date(2025, 6, 1), CalendarPeriod::Year, Weekday::Mon, 1)
+    );
+    let ranges = get_calendar_date_ranges(
+        &date(2027, 6, 1),
+        CalendarPeriod::FiveYears,
+        Weekday::Mon,
+        1,
+    );
+    assert_eq!(vec![(date(2027, 6, 1), date(2025, 1, 1))], ranges);
+    assert_eq!("2025-2029", CalendarPeriod::FiveYears.label(&ranges[0].1));
+
+    // The ranges stop at the earliest supported date
+    assert_eq!(
+        1,
+        get_calendar_date_ranges(&NaiveDate::MIN, CalendarPeriod::Month, Weekday::Mon, 3).len()
+    );
+}
+
+#[test]
+fn test_numeric_values() {
+    let mut data = open_test_database(&["PNO", "GAM"]);
+    let day1 = NaiveDate::from_ymd_opt(2000, 5, 1).unwrap();
+    let day2 = NaiveDate::from_ymd_opt(2000, 5, 2).unwrap();
+    data.set_value(&day1, CategoryId(1), Some(30.0)).unwrap();
+    data.update_data(&day2, &ids([1, 2])).unwrap();
+    data.set_value(&day2, CategoryId(1), Some(15.0)).unwrap();
+
+    // Toggling another category keeps the value
+    data.update_data(&day2, &ids([1])).unwrap();
+    assert_eq!(Some(15.0), data.get_value(&day2, CategoryId(1)).unwrap());
+    assert_eq!(Some(ids([1])), data.get_row(&day1).unwrap());
+
+    let stats = data
+        .calculate_data_counts_per_iter(&[(day2, day1)])
+        .unwrap();
+    assert_eq!(
+        vec![vec![
+            CategoryStats {
+                count: 2,
+                partial_count: 0,
+                skipped_count: 0,
+                sum: 45.0,
+                value_count: 2,
+                scheduled_days: 2,
+                excused_days: 0
+            },
+            CategoryStats {
+                scheduled_days: 2,
+                ..Default::default()
+            }
+        ]],
+        stats
+    );
+    assert_eq!(Some(22.5), stats[0][0].average());
+}
+
+#[test]
+fn test_notes() {
+    let mut data = open_test_database(&["PNO"]);
+    let day = NaiveDate::from_ymd_opt(2000, 5, 1).unwrap();
+    assert_eq!(None, data.get_note(&day).unwrap());
+    data.set_note(&day, "first").unwrap();
+    data.set_note(&day, "second").unwrap();
+    assert_eq!(Some(String::from("second")), data.get_note(&day).unwrap());
+
+    // Notes are independent of the habit data of the day
+    data.update_data(&day, &ids([1])).unwrap();
+    assert_eq!(Some(String::from("second")), data.get_note(&day).unwrap());
+    data.set_note(&day, "").unwrap();
+    assert_eq!(None, data.get_note(&day).unwrap());
+}
+
+#[test]
+fn test_targets() {
+    let data = open_test_database(&["PNO", "GAM"]);
+    let at_least = Target {
+        kind: TargetKind::AtLeast,
+        count: 4,
+        days: 7,
+    };
+    assert_eq!(
+        UpdateCategoryResult::Updated,
+        data.set_target("PNO", Some(at_least)).unwrap()
+    );
+    assert_eq!(
+        UpdateCategoryResult::NonExistingCategory,
+        data.set_target("RUN", Some(at_least)).unwrap()
+    );
+    assert_eq!(
+        HashMap::from([(CategoryId(1), at_least)]),
+        data.get_targets().unwrap()
+    );
+    let daily = Schedule::default();
+    assert!(at_least.is_met(8.0, &daily, 14));
+    assert!(!at_least.is_met(7.5, &daily, 14));
+    // The target refers to the scheduled days of its period
+    let weekdays = Schedule::Weekdays(WeekdaySet::from_array([
+        Weekday::Mon,
+        Weekday::Wed,
+        Weekday::Fri,
+    ]));
+    assert!(at_least.is_met(4.0, &weekdays, 3));
+    assert!(!at_least.is_met(3.0, &weekdays, 3));
+    assert!(at_least.is_met(2.0, &weekdays, 1));
+    assert!(!at_least.is_met(1.0, &weekdays, 1));
+
+    data.set_target("PNO", None).unwrap();
+    assert!(data.get_targets().unwrap().is_empty());
+}
+
+#[test]
+fn test_category_management() {
+    let mut data = open_test_database(&["PNO", "GAM", "RUN"]);
+    let day1 = NaiveDate::from_ymd_opt(2000, 5, 1).unwrap();
+    let day2 = NaiveDate::from_ymd_opt(2000, 5, 2).unwrap();
+    data.update_data(&day1, &ids([1, 3])).unwrap();
+    data.update_data(&day2, &ids([3])).unwrap();
+    data.set_value(&day1, CategoryId(1), Some(10.0)).unwrap();
+    data.set_value(&day1, CategoryId(3), Some(5.0)).unwrap();
+
+    assert_eq!(
+        RenameCategoryResult::NameTaken,
+        data.rename_category("RUN", "GAM").unwrap()
+    );
+    assert_eq!(
+        RenameCategoryResult::InvalidName,
+        data.rename_category("RUN", " ").unwrap()
+    );
+    assert_eq!(
+        RenameCategoryResult::Renamed,
+        data.rename_category("RUN", "JOG").unwrap()
+    );
+    data.reorder_categories(&[String::from("JOG")]).unwrap();
+    assert_eq!(
+        vec![
+            (String::from("JOG"), CategoryId(3)),
+            (String::from("PNO"), CategoryId(1)),
+            (String::from("GAM"), CategoryId(2))
+        ],
+        data.get_header()
+            .unwrap()
+            .into_iter()
+            .map(|category| (category.name, category.id))
+            .collect::<Vec<_>>()
+    );
+
+    assert_eq!(
+        MergeCategoryResult::Merged,
+        data.merge_category("JOG", "PNO").unwrap()
+    );
+    assert_eq!(Some(ids([1])), data.get_row(&day1).unwrap());
+    assert_eq!(Some(ids([1])), data.get_row(&day2).unwrap());
+    assert_eq!(Some(15.0), data.get_value(&day1, CategoryId(1)).unwrap());
+
+    assert_eq!(
+        UpdateCategoryResult::Updated,
+        data.delete_category("PNO").unwrap()
+    );
+    assert_eq!(Some(ids([])), data.get_row(&day1).unwrap());
+    assert_eq!(1, data.get_categories().unwrap().len());
+}
+
+#[test]
+fn test_streaks() {
+    let mut data = open_test_database(&["PNO", "GAM"]);
+    data.set_category_polarity("GAM", Polarity::Avoid).unwrap();
+    let date = |day| NaiveDate::from_ymd_opt(2000, 5, day).unwrap();
+    data.update_data(&date(1), &ids([1, 2])).unwrap();
+    data.update_data(&date(2), &ids([1])).unwrap();
+    data.update_data(&date(3), &ids([1])).unwrap();
+    data.update_data(&date(4), &ids([])).unwrap();
+    data.update_data(&date(5), &ids([1])).unwrap();
+    data.update_data(&date(6), &ids([1])).unwrap();
+
+    assert_eq!(2, data.get_streak(CategoryId(1), &date(6)).unwrap());
+    assert_eq!(2, data.get_streak(CategoryId(1), &date(7)).unwrap());
+    assert_eq!(0, data.get_streak(CategoryId(1), &date(8)).unwrap());
+    assert_eq!(3, data.get_streak(CategoryId(1), &date(3)).unwrap());
+    assert_eq!(5, data.get_streak(CategoryId(2), &date(6)).unwrap());
+    // The habits to avoid are left out of the most frequent compositions
+    assert_eq!(
+        vec![(ids([1]), 5)],
+        data.get_most_frequent_daily_data(&None, &date(6), None)
+            .unwrap()
+    );
+    assert_eq!(
+        Some(5),
+        data.get_days_since_last_occurrence(CategoryId(2), &date(6))
+            .unwrap()
+    );
+    let before_first_date = NaiveDate::from_ymd_opt(2000, 4, 30).unwrap();
+    assert_eq!(
+        None,
+        data.get_days_since_last_occurrence(CategoryId(1), &before_first_date)
+            .unwrap()
+    );
+}
+
+#[test]
+fn test_schedules() {
+    let mut data = open_test_database(&["GYM"]);
+    // 2000-05-01 is a Monday
+    let date = |day| NaiveDate::from_ymd_opt(2000, 5, day).unwrap();
+    let schedule = Schedule::Weekdays(WeekdaySet::from_array([
+        Weekday::Mon,
+        Weekday::Wed,
+        Weekday::Fri,
+    ]));
+    data.set_category_schedule("GYM", &schedule).unwrap();
+    assert_eq!(schedule, data.get_categories().unwrap()[0].schedule);
+    data.update_data(&date(1), &ids([1])).unwrap();
+    data.update_data(&date(2), &ids([1])).unwrap();
+    data.update_data(&date(3), &ids([1])).unwrap();
+    data.update_data(&date(5), &ids([1])).unwrap();
+
+    let stats = data
+        .calculate_data_counts_per_iter(&[(date(7), date(1))])
+        .unwrap();
+    assert_eq!(3, stats[0][0].count);
+    assert_eq!(3, stats[0][0].scheduled_days);
+    assert_eq!(4, data.get_streak(CategoryId(1), &date(7)).unwrap());
+
+    let schedule = Schedule::EveryNDays {
+        days: 2,
+        anchor: date(1),
+    };
+    data.set_category_schedule("GYM", &schedule).unwrap();
+    assert_eq!(schedule, data.get_categories().unwrap()[0].schedule);
+    let stats = data
+        .calculate_data_counts_per_iter(&[(date(7), date(1))])
+        .unwrap();
+    assert_eq!(3, stats[0][0].count);
+    assert_eq!(4, stats[0][0].scheduled_days);
+}
+
+#[test]
+fn test_schedules_before_1970() {
+    let mut data = open_test_database(&["GYM"]);
+    // 1960-05-02 is a Monday
+    let date = |day| NaiveDate::from_ymd_opt(1960, 5, day).unwrap();
+    let schedule = Schedule::Weekdays(WeekdaySet::from_array([Weekday::Mon, Weekday::Thu]));
+    data.set_category_schedule("GYM", &schedule).unwrap();
+    data.update_data(&date(2), &ids([1])).unwrap();
+    data.update_data(&date(3), &ids([1])).unwrap();
+    data.update_data(&date(5), &ids([1])).unwrap();
+
+    let stats = data
+        .calculate_data_counts_per_iter(&[(date(8), date(2))])
+        .unwrap();
+    assert_eq!(2, stats[0][0].count);
+    assert_eq!(2, stats[0][0].scheduled_days);
+}
+
+#[test]
+fn test_count_scheduled_days() {
+    let date = |day| NaiveDate::from_ymd_opt(1969, 12, 1).unwrap() + TimeDelta::days(day);
+    let schedules = [
+        Schedule::default(),
+        Schedule::Weekdays(WeekdaySet::from_array([Weekday::Tue, Weekday::Sun])),
+        Schedule::EveryNDays {
+            days: 3,
+            anchor: date(20),
+        },
+        Schedule::EveryNDays {
+            days: 5,
+            anchor: date(-7),
+        },
+    ];
+    for schedule in schedules {
+        for from in 0..16 {
+            for until in from - 1..from + 30 {
+                let expected = date(from)
+                    .iter_days()
+                    .take_while(|day| *day <= date(until))
+                    .filter(|day| schedule.is_scheduled(day))
+                    .count();
+                assert_eq!(
+                    expected,
+                    schedule.count_scheduled_days(&date(from), &date(until)),
+                    "{} from {} until {}",
+                    schedule,
+                    date(from),
+                    date(until)
+                );
+            }
+        }
+    }
+
+    // Overlapping excused ranges are counted once
+    let mut data = open_test_database(&["GYM"]);
+    data.set_excused(None, &date(3), &date(6), true).unwrap();
+    data.set_excused(Some(CategoryId(1)), &date(5), &date(9), true)
+        .unwrap();
+    let stats = data
+        .calculate_data_counts_per_iter(&[(date(13), date(0)), (date(7), date(4))])
+        .unwrap();
+    assert_eq!(
+        (7, 7),
+        (stats[0][0].scheduled_days, stats[0][0].excused_days)
+    );
+    assert_eq!(
+        (0, 4),
+        (stats[1][0].scheduled_days, stats[1][0].excused_days)
+    );
+}
+
+#[test]
+fn test_excused_days() {
+    let mut data = open_test_database(&["GYM", "READ"]);
+    let date = |day| NaiveDate::from_ymd_opt(2000, 5, day).unwrap();
+    data.update_data(&date(1), &ids([1, 2])).unwrap();
+    data.update_data(&date(2), &ids([1, 2])).unwrap();
+    data.update_data(&date(6), &ids([1, 2])).unwrap();
+    data.update_data(&date(7), &ids([1, 2])).unwrap();
+
+    data.set_excused(None, &date(3), &date(4), true).unwrap();
+    data.set_excused(None, &date(5), &date(5), true).unwrap();
+    data.set_excused(Some(CategoryId(2)), &date(8), &date(9), true)
+        .unwrap();
+    assert_eq!(
+        vec![
+            ExcusedRange {
+                category_id: None,
+                from: date(3),
+                until: date(5)
+            },
+            ExcusedRange {
+                category_id: Some(CategoryId(2)),
+                from: date(8),
+                until: date(9)
+            }
+        ],
+        data.get_excused_ranges().unwrap()
+    );
+    assert_eq!(
+        vec![date(8), date(9)],
+        data.get_missing_dates(&None, &date(9)).unwrap()
+    );
+    let days = data.get_days(&date(7), &date(9)).unwrap();
+    assert_eq!(None, days[2].entries);
+    assert_eq!(ids([2]), days[2].excused_categories);
+    assert!(days[2].is_excused(CategoryId(2)));
+    assert!(!days[0].excused);
+    assert!(data.get_days(&date(4), &date(4)).unwrap()[0].excused);
+
+    let stats = data
+        .calculate_data_counts_per_iter(&[(date(9), date(1))])
+        .unwrap();
+    assert_eq!(
+        (6, 3),
+        (stats[0][0].scheduled_days, stats[0][0].excused_days)
+    );
+    assert_eq!(
+        (4, 5),
+        (stats[0][1].scheduled_days, stats[0][1].excused_days)
+    );
+    assert_eq!(4, data.get_streak(CategoryId(1), &date(7)).unwrap());
+    assert_eq!(4, data.get_streak(CategoryId(2), &date(9)).unwrap());
+
+    data.set_excused(None, &date(4), &date(4), false).unwrap();
+    let ranges = data.get_excused_ranges().unwrap();
+    assert_eq!((date(3), date(3)), (ranges[0].from, ranges[0].until));
+    assert_eq!((date(5), date(5)), (ranges[1].from, ranges[1].until));
+    assert_eq!(2, data.get_streak(CategoryId(1), &date(7)).unwrap());
+}
+
+#[test]
+fn test_entry_statuses() {
+    let mut data = open_test_database(&["GYM", "READ"]);
+    let date = |day| NaiveDate::from_ymd_opt(2000, 5, day).unwrap();
+    data.update_data(&date(1), &ids([1, 2])).unwrap();
+    data.set_status(&date(2), CategoryId(1), Some(EntryStatus::Partial))
+        .unwrap();
+    data.set_status(&date(3), CategoryId(1), Some(EntryStatus::Skipped))
+        .unwrap();
+    data.set_status(&date(4), CategoryId(1), Some(EntryStatus::Done))
+        .unwrap();
+    data.set_value(&date(4), CategoryId(1), Some(10.0)).unwrap();
+    data.set_status(&date(4), CategoryId(2), Some(EntryStatus::Skipped))
+        .unwrap();
+    assert_eq!(
+        vec![
+            (date(3), CategoryId(1), EntryStatus::Skipped),
+            (date(4), CategoryId(1), EntryStatus::Done),
+            (date(4), CategoryId(2), EntryStatus::Skipped)
+        ],
+        data.get_statuses(&date(3), &date(4)).unwrap()
+    );
+    assert_eq!(Some(10.0), data.get_value(&date(4), CategoryId(1)).unwrap());
+
+    let stats = data
+        .calculate_data_counts_per_iter(&[(date(4), date(1))])
+        .unwrap();
+    assert_eq!(
+        (3, 1, 1, 2.5),
+        (
+            stats[0][0].count,
+            stats[0][0].partial_count,
+            stats[0][0].skipped_count,
+            stats[0][0].weighted_count()
+        )
+    );
+    assert_eq!(1, stats[0][1].count);
+    assert_eq!(3, data.get_streak(CategoryId(1), &date(4)).unwrap());
+    assert_eq!(
+        Some(3),
+        data.get_days_since_last_occurrence(CategoryId(2), &date(4))
+            .unwrap()
+    );
+    assert_eq!(
+        vec![(ids([1]), 2), (ids([1, 2]), 1)],
+        data.get_most_frequent_daily_data(&None, &date(4), None)
+            .unwrap()
+    );
+
+    // Rewriting the day keeps the status of the categories that stay checked
+    data.update_data(&date(2), &ids([1, 2])).unwrap();
+    assert_eq!(
+        vec![
+            (date(2), CategoryId(1), EntryStatus::Partial),
+            (date(2), CategoryId(2), EntryStatus::Done)
+        ],
+        data.get_statuses(&date(2), &date(2)).unwrap()
+    );
+    data.set_status(&date(2), CategoryId(1), None).unwrap();
+    assert_eq!(Some(ids([2])), data.get_row(&date(2)).unwrap());
+}
+
+#[cfg(test)]
+fn get_schema(connection: &Connection) -> Vec<(String, String)> {
+    let mut statement = connection
+        .prepare(
+            "SELECT m.name, p.name FROM sqlite_master AS m, pragma_table_info(m.name) AS p
+                WHERE m.type='table' AND m.name NOT LIKE 'sqlite_%' ORDER BY m.name, p.name",
+        )
+        .unwrap();
+    statement
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .unwrap()
+        .collect::<Result<_, _>>()
+        .unwrap()
+}
+
+#[test]
+fn test_migrations() {
+    // Schema of the datafiles before versioning was introduced
+    let connection = Connection::open_in_memory().unwrap();
+    connection
+        .execute_batch(
+            "CREATE TABLE Category(
+                category_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE DateEntry(
+                date DATE PRIMARY KEY,
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE EntryToCategories(
+                date INTEGER NOT NULL REFERENCES DateEntry(date) ON DELETE CASCADE,
+                category_id INTEGER NOT NULL REFERENCES Category(category_id) ON DELETE CASCADE,
+                PRIMARY KEY(category_id, date)
+            );
+            INSERT INTO Category (name, created_at) VALUES ('GYM', 0);
+            INSERT INTO DateEntry (date, created_at) VALUES (0, 0);
+            INSERT INTO EntryToCategories (date, category_id) VALUES (0, 1);
+            INSERT INTO DateEntry (date, created_at) VALUES (957182400, 0);
+            INSERT INTO EntryToCategories (date, category_id) VALUES (957182400, 1);",
+        )
+        .unwrap();
+    assert_eq!(0, migration::get_version(&connection).unwrap());
+    assert_eq!(
+        migration::latest_version(),
+        migration::get_pending(0).unwrap().len()
+    );
+
+    let data = open_sqlite_database(connection).unwrap();
+    assert_eq!(
+        migration::latest_version(),
+        migration::get_version(&data.connection).unwrap()
+    );
+    assert!(
+        migration::get_pending(migration::latest_version())
+            .unwrap()
+            .is_empty()
+    );
+    assert_eq!(
+        Some(ids([1])),
+        data.get_row(&NaiveDate::from_ymd_opt(1970, 1, 1).unwrap())
+            .unwrap()
+    );
+    // Timestamps are converted to the day they fall on in UTC
+    assert_eq!(
+        Some(ids([1])),
+        data.get_row(&NaiveDate::from_ymd_opt(2000, 5, 1).unwrap())
+            .unwrap()
+    );
+    let fresh = open_test_database(&[]);
+    assert_eq!(get_schema(&fresh.connection), get_schema(&data.connection));
+
+    // Datafiles written by a newer version are refused
+    migration::set_version(&data.connection, migration::latest_version() + 1).unwrap();
+    assert!(matches!(
+        open_sqlite_database(data.connection),
+        Err(Error::UnsupportedVersion { .. })
+    ));
+}
+
+#[test]
+fn test_day_number_migration() {
+    let mut connection = Connection::open_in_memory().unwrap();
+    connection
+        .execute_batch(
+            "CREATE TABLE Category(
+                category_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE DateEntry(
+                date DATE PRIMARY KEY,
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE EntryToCategories(
+                date INTEGER NOT NULL REFERENCES DateEntry(date) ON DELETE CASCADE,
+                category_id INTEGER NOT NULL REFERENCES Category(category_id) ON DELETE CASCADE,
+                PRIMARY KEY(category_id, date)
+            );
+            INSERT INTO Category (name, created_at) VALUES ('GYM', 0);",
+        )
+        .unwrap();
+    migration::run_until(&mut connection, 10).unwrap();
+
+    // Rows recorded at different times of the same day are merged
+    connection
+        .execute_batch(
+            "INSERT INTO DateEntry (date, created_at) VALUES (957182400, 0), (957204000, 0);
+            INSERT INTO EntryToCategories (date, category_id, value, status)
+                VALUES (957182400, 1, 2.0, 1), (957204000, 1, 5.0, 0);
+            INSERT INTO DateNote (date, note, created_at)
+                VALUES (957182400, 'morning', 0), (957204000, 'evening', 0);",
+        )
+        .unwrap();
+    let data = open_sqlite_database(connection).unwrap();
+    let day = NaiveDate::from_ymd_opt(2000, 5, 1).unwrap();
+    assert_eq!(Some(ids([1])), data.get_row(&day).unwrap());
+    assert_eq!(
+        vec![(day, CategoryId(1), 5.0)],
+        data.get_values(&day, &day).unwrap()
+    );
+    assert_eq!(
+        vec![(day, CategoryId(1), EntryStatus::Done)],
+        data.get_statuses(&day, &day).unwrap()
+    );
+    assert_eq!(
+        Some(String::from("morning\nevening")),
+        data.get_note(&day).unwrap()
+    );
+}
+
+#[test]
+fn test_doctor() {
+    let mut data = open_test_database(&["PNO", "GAM"]);
+    let day = NaiveDate::from_ymd_opt(2000, 5, 1).unwrap();
+    data.update_data(&day, &ids([1, 2])).unwrap();
+    assert!(data.check_integrity().unwrap().is_healthy());
+
+    // Corruptions possible in datafiles written without foreign key enforcement or by other tools
+    let day_number = date_to_day(&day);
+    data.connection
+        .execute_batch(&format!(
+            "PRAGMA foreign_keys=OFF;
+            INSERT INTO EntryToCategories (date, category_id) VALUES ({day_number}, 5);
+            INSERT INTO EntryToCategories (date, category_id) VALUES ({}, 1);
+            INSERT INTO ExcusedRange (category_id, from_date, until_date) VALUES (7, 0, 0);
+            INSERT INTO ExcusedRange (from_date, until_date) VALUES ('2000-05-03', 0);
+            UPDATE Category SET name='PNO' WHERE category_id=2;
+            UPDATE Category SET schedule_every=2, schedule_anchor=1e12 WHERE category_id=1;
+            PRAGMA foreign_keys=ON;",
+            day_number + 1
+        ))
+        .unwrap();
+    let report = data.check_integrity().unwrap();
+    assert!(!report.is_healthy());
+    assert_eq!(migration::latest_version(), report.version);
+    assert_eq!(Some((day, day)), report.date_range);
+    assert_eq!(2, report.categories.len());
+    assert_eq!(2, report.orphan_entries);
+    assert_eq!(1, report.orphan_excused_ranges);
+    assert_eq!(vec![String::from("PNO")], report.duplicate_names);
+    assert_eq!(2, report.invalid_dates);
+
+    data.repair().unwrap();
+    let report = data.check_integrity().unwrap();
+    assert!(report.is_healthy());
+    assert_eq!(Some((day, day.succ_opt().unwrap())), report.date_range);
+    assert_eq!(Some(ids([1, 2])), data.get_row(&day).unwrap());
+    assert_eq!(
+        Some(ids([1])),
+        data.get_row(&day.succ_opt().unwrap()).unwrap()
+    );
+    assert_eq!(Schedule::default(), report.categories[0].schedule);
+    assert_eq!(
+        vec!["PNO", "PNO-2"],
+        report
+            .categories
+            .iter()
+            .map(|category| category.name.as_str())
+            .collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_doctor_old_datafile() {
+    let dir = std::env::temp_dir().join(format!("genee_doctor_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("diary.db");
+
+    // Datafile of the first schema version with an entry of a missing category
+    let connection = Connection::open(&path).unwrap();
+    connection
+        .execute_batch(
+            "PRAGMA foreign_keys=OFF;
+            CREATE TABLE Info(
+                info_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                info_name TEXT UNIQUE NOT NULL,
+                info_value TEXT NOT NULL
+            );
+            CREATE TABLE Category(
+                category_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                name TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                hidden INTEGER NOT NULL
+            );
+            CREATE TABLE DateEntry(
+                date DATE PRIMARY KEY,
+                created_at INTEGER NOT NULL
+            );
+            CREATE TABLE EntryToCategories(
+                date INTEGER NOT NULL REFERENCES DateEntry(date) ON DELETE CASCADE,
+                category_id INTEGER NOT NULL REFERENCES Category(category_id) ON DELETE CASCADE,
+                PRIMARY KEY(category_id, date)
+            );
+            INSERT INTO Info (info_name, info_value) VALUES ('version', '1');
+            INSERT INTO Category (name, created_at, hidden) VALUES ('GYM', 0, 0);
+            INSERT INTO DateEntry (date, created_at) VALUES (957182400, 0);
+            INSERT INTO EntryToCategories (date, category_id) VALUES (957182400, 1);
+            INSERT INTO EntryToCategories (date, category_id) VALUES (957182400, 5);",
+        )
+        .unwrap();
+    drop(connection);
+
+    // The existing violations do not prevent the migrations, so the doctor can repair them
+    let mut data = open_datafile(&path, None).unwrap();
+    let report = data.check_integrity().unwrap();
+    assert_eq!(migration::latest_version(), report.version);
+    assert_eq!(1, report.orphan_entries);
+
+    // The backup is made before the repair even if the automatic backups are disabled
+    let policy = BackupPolicy {
+        directory: get_default_backup_dir(&path),
+        count: 0,
+    };
+    let backup = data.repair_with_backup(&path, &policy).unwrap();
+    assert!(backup.path.exists());
+    assert_eq!(vec![backup], list_backups(&path, &policy).unwrap());
+    assert!(data.check_integrity().unwrap().is_healthy());
+    let day = NaiveDate::from_ymd_opt(2000, 5, 1).unwrap();
+    assert_eq!(Some(ids([1])), data.get_row(&day).unwrap());
+    drop(data);
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_errors() {
+    let data = open_test_database(&["PNO"]);
+    assert!(matches!(data.get_date_range(), Err(Error::EmptyDiary)));
+
+    // Corrupt values are reported instead of panicking
+    data.connection
+        .execute_batch(
+            "INSERT INTO DateEntry (date, created_at) VALUES (1e12, 0);
+            UPDATE Info SET info_value='latest' WHERE info_name='version';",
+        )
+        .unwrap();
+    assert!(matches!(data.get_date_range(), Err(Error::Sqlite(_))));
+    assert!(matches!(
+        migration::get_version(&data.connection),
+        Err(Error::CorruptData(_))
+    ));
+
+    let mut memory = DiaryDataMemory::new(&[String::from("PNO")]);
+    let day = NaiveDate::from_ymd_opt(2000, 5, 1).unwrap();
+    assert!(matches!(
+        memory.set_value(&day, CategoryId(2), Some(1.0)),
+        Err(Error::NonExistingCategory(CategoryId(2)))
+    ));
+}
+
+#[test]
+fn test_delimited_export() {
+    let date = |day| NaiveDate::from_ymd_opt(2000, 5, day).unwrap();
+    let mut data = open_test_database(&["PNO", "RUN", "OLD"]);
+    data.update_data(&date(1), &ids([1, 3])).unwrap();
+    data.set_value(&date(1), CategoryId(2), Some(5.5)).unwrap();
+    data.set_status(&date(3), CategoryId(1), Some(EntryStatus::Partial))
+        .unwrap();
+    data.set_status(&date(3), CategoryId(2), Some(EntryStatus::Skipped))
+        .unwrap();
+    data.hide_category("OLD").unwrap();
+    let export = |options: &DelimitedExportOptions| {
+        let mut output = vec![];
+        let row_count = export_delimited(&data, &mut output, &date(1), &date(3), options).unwrap();
+        (row_count, String::from_utf8(output).unwrap())
+    };
+
+    let mut options = DelimitedExportOptions {
+        delimiter: b',',
+        include_hidden: false,
+        untracked: UntrackedDays::Empty,
+    };
+    assert_eq!(
+        (
+            3,
+            String::from(
+                "date,PNO,RUN\n2000-05-01,1,5.5\n2000-05-02,,\n2000-05-03,partial,skipped\n"
+            )
+        ),
+        export(&options)
+    );
+    options.delimiter = b'\t';
+    options.include_hidden = true;
+    options.untracked = UntrackedDays::Zero;
+    assert_eq!(
+        (
+            3,
+            String::from(
+                "date\tPNO\tRUN\tOLD\n2000-05-01\t1\t5.5\t1\n2000-05-02\t0\t0\t0\n2000-05-03\tpartial\tskipped\t0\n"
+            )
+        ),
+        export(&options)
+    );
+    options.untracked = UntrackedDays::Omit;
+    assert_eq!(2, export(&options).0);
+}
+
+#[test]
+fn test_delimited_import() {
+    let date = |day| NaiveDate::from_ymd_opt(2000, 5, day).unwrap();
+    let mut data = open_test_database(&["PNO", "RUN", "GAM"]);
+    data.update_data(&date(2), &ids([2, 3])).unwrap();
+    let csv = "Day;Piano;RUN;Comment\n2000-05-01;X;12;\n2000-05-02;✓;3;done\n2000-05-03;no;;\n";
+    let mut options = DelimitedImportOptions {
+        delimiter: b';',
+        mapping: vec![(String::from("Piano"), CategoryId(1))],
+        threshold: Some(10.0),
+        ..Default::default()
+    };
+
+    // A dry run only reports, the tracked days are skipped by default
+    let report = import_delimited(&mut data, csv.as_bytes(), &options, true).unwrap();
+    assert_eq!(
+        vec![
+            (String::from("Piano"), CategoryId(1)),
+            (String::from("RUN"), CategoryId(2))
+        ],
+        report.mapped_columns
+    );
+    assert_eq!(vec![String::from("Comment")], report.ignored_columns);
+    assert_eq!(
+        (1, 1, 0, 2),
+        (
+            report.new_days,
+            report.skipped_days,
+            report.updated_days,
+            report.entries
+        )
+    );
+    assert_eq!(
+        vec!["no"],
+        report.unrecognized_values.iter().collect::<Vec<_>>()
+    );
+    assert_eq!(None, data.get_row(&date(1)).unwrap());
+
+    import_delimited(&mut data, csv.as_bytes(), &options, false).unwrap();
+    assert_eq!(Some(ids([1, 2])), data.get_row(&date(1)).unwrap());
+    assert_eq!(Some(ids([2, 3])), data.get_row(&date(2)).unwrap());
+    assert_eq!(None, data.get_row(&date(3)).unwrap());
+    // The numeric cells are imported as values
+    let day = &data.get_days(&date(1), &date(1)).unwrap()[0];
+    assert_eq!(
+        vec![None, Some(12.0)],
+        day.entries
+            .iter()
+            .flatten()
+            .map(|entry| entry.value)
+            .collect::<Vec<_>>()
+    );
+
+    // Overwriting replaces the imported categories only, even by the rows without any done cell,
+    // the union keeps the existing entries
+    options.conflict_policy = ConflictPolicy::Overwrite;
+    data.update_data(&date(3), &ids([1, 3])).unwrap();
+    import_delimited(&mut data, csv.as_bytes(), &options, false).unwrap();
+    assert_eq!(Some(ids([1, 3])), data.get_row(&date(2)).unwrap());
+    assert_eq!(Some(ids([3])), data.get_row(&date(3)).unwrap());
+    data.update_data(&date(2), &ids([2, 3])).unwrap();
+    options.conflict_policy = ConflictPolicy::Union;
+    let report = import_delimited(&mut data, csv.as_bytes(), &options, false).unwrap();
+    assert_eq!(2, report.updated_days);
+    assert_eq!(Some(ids([1, 2, 3])), data.get_row(&date(2)).unwrap());
+
+    // A failed import does not write anything
+    options.mapping = vec![(String::from("Piano"), CategoryId(9))];
+    options.conflict_policy = ConflictPolicy::Overwrite;
+    data.update_data(&date(1), &ids([])).unwrap();
+    assert!(import_delimited(&mut data, csv.as_bytes(), &options, false).is_err());
+    assert_eq!(Some(ids([])), data.get_row(&date(1)).unwrap());
+    assert_eq!(Some(ids([1, 2, 3])), data.get_row(&date(2)).unwrap());
+
+    // Without a threshold, any positive number marks an entry as done
+    let numbers = "date,GAM\n2000-05-04,0\n2000-05-05,0.5\n";
+    let report =
+        import_delimited(&mut data, numbers.as_bytes(), &Default::default(), false).unwrap();
+    assert_eq!((1, 1), (report.new_days, report.entries));
+    assert_eq!(None, data.get_row(&date(4)).unwrap());
+    assert_eq!(Some(ids([3])), data.get_row(&date(5)).unwrap());
+
+    let malformed = "date,PNO\n2000-05-01,x\n05/02/2000,x\n";
+    assert!(matches!(
+        import_delimited(&mut data, malformed.as_bytes(), &Default::default(), false),
+        Err(Error::InvalidInput(_))
+    ));
+}
+
+#[test]
+fn test_activity_import() {
+    let date = |day| NaiveDate::from_ymd_opt(2000, 5, day).unwrap();
+    let daylio = "full_date,date,weekday,time,mood,activities,note_title,note
+2000-05-03,May 3,Wednesday,20:00,good,reading | sport,,
+2000-05-02,May 2,Tuesday,21:00,meh,,,
+2000-05-01,May 1,Monday,22:00,rad,sport | friends,,
+2000-05-01,May 1,Monday,10:00,good,reading,,
+";
+    let log = read_daylio_export(daylio.as_bytes()).unwrap();
+    assert_eq!(vec!["sport", "friends", "reading"], log.activities);
+    assert_eq!(
+        vec![date(1), date(2), date(3)],
+        log.days.keys().copied().collect::<Vec<_>>()
+    );
+    assert_eq!(2, log.count_days("sport"));
+    assert!(log.days[&date(2)].is_empty());
+
+    // The history entries belong to the day before, the old ones are completed if the value increased
+    let habitica = r#"{"tasks": {"habits": [], "dailys": [
+        {"text": "Floss", "history": [
+            {"date": 957268800000, "value": 1.5, "isDue": true, "completed": true},
+            {"date": 957355200000, "value": 0.5, "isDue": true, "completed": false}]},
+        {"text": "Stretch", "history": [
+            {"date": "2000-05-03T12:00:00Z", "value": 0.0},
+            {"date": "2000-05-04T12:00:00Z", "value": 1.0}]}
+    ]}}"#;
+    let log = read_habitica_export(habitica.as_bytes()).unwrap();
+    assert_eq!(vec!["Floss", "Stretch"], log.activities);
+    assert_eq!(
+        vec![
+            (date(1), vec!["Floss"]),
+            (date(2), vec![]),
+            (date(3), vec!["Stretch"])
+        ],
+        log.days
+            .iter()
+            .map(|(date, activities)| (*date, activities.iter().map(String::as_str).collect()))
+            .collect::<Vec<(NaiveDate, Vec<&str>)>>()
+    );
+    assert!(matches!(
+        read_habitica_export("{}".as_bytes()),
+        Err(Error::InvalidInput(_))
+    ));
+
+    // The unmapped activities are ignored and the missing categories are created
+    let log = read_daylio_export(daylio.as_bytes()).unwrap();
+    let mut data = open_test_database(&["GYM"]);
+    let mapping = vec![
+        (String::from("sport"), String::from("GYM")),
+        (String::from("reading"), String::from("READ")),
+    ];
+    let report = import_activities(&mut data, &log, &mapping, ConflictPolicy::Skip, true).unwrap();
+    assert_eq!(vec![String::from("friends")], report.ignored_columns);
+    assert_eq!("READ", report.created_categories[0].name);
+    assert_eq!((3, 4), (report.new_days, report.entries));
+    assert!(data.is_empty().unwrap());
+
+    import_activities(&mut data, &log, &mapping, ConflictPolicy::Skip, false).unwrap();
+    assert_eq!(2, data.get_categories().unwrap().len());
+    assert_eq!(Some(ids([1, 2])), data.get_row(&date(1)).unwrap());
+    assert_eq!(Some(ids([])), data.get_row(&date(2)).unwrap());
+    assert_eq!(Some(ids([1, 2])), data.get_row(&date(3)).unwrap());
+}
+
+#[test]
+fn test_loop_import() {
+    let dir = std::env::temp_dir().join(format!("genee_loop_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("loop.db");
+    let backup = Connection::open(&path).unwrap();
+    backup
+        .execute_batch(
+            "CREATE TABLE Habits (id INTEGER PRIMARY KEY, archived INTEGER, name TEXT,
+                position INTEGER, type INTEGER NOT NULL DEFAULT 0);
+            CREATE TABLE Repetitions (id INTEGER PRIMARY KEY, habit INTEGER NOT NULL,
+                timestamp INTEGER NOT NULL, value INTEGER NOT NULL);
+            INSERT INTO Habits VALUES (1, 0, 'PNO', 0, 0), (2, 0, 'Run', 1, 1), (3, 1, 'Old', 2, 0);
+            -- 2000-05-01 and 2000-05-02 at midnight UTC
+            INSERT INTO Repetitions (habit, timestamp, value) VALUES
+                (1, 957139200000, 2), (1, 957225600000, 3), (2, 957139200000, 5500),
+                (2, 957225600000, 0), (3, 957225600000, 2), (3, 957139200000, 1);",
+        )
+        .unwrap();
+    drop(backup);
+    let date = |day| NaiveDate::from_ymd_opt(2000, 5, day).unwrap();
+    let mut data = open_test_database(&["PNO"]);
+
+    // A dry run does not create the categories
+    let report = import_loop_backup(&mut data, &path, ConflictPolicy::Skip, true).unwrap();
+    let created: Vec<_> = report
+        .created_categories
+        .iter()
+        .map(|category| (category.name.as_str(), category.hidden))
+        .collect();
+    assert_eq!(vec![("Run", false), ("Old", true)], created);
+    assert_eq!((2, 4), (report.new_days, report.entries));
+    assert_eq!(1, data.get_categories().unwrap().len());
+
+    // The archived habits become hidden categories
+    let report = import_loop_backup(&mut data, &path, ConflictPolicy::Skip, false).unwrap();
+    assert_eq!(
+        vec![
+            (String::from("PNO"), CategoryId(1)),
+            (String::from("Run"), CategoryId(2)),
+            (String::from("Old"), CategoryId(3))
+        ],
+        report.mapped_columns
+    );
+    assert_eq!(ids([1, 2]), data.get_row(&date(1)).unwrap().unwrap());
+    assert_eq!(ids([1]), data.get_row(&date(2)).unwrap().unwrap());
+    let records = data.get_days(&date(1), &date(2)).unwrap();
+    assert_eq!(
+        Some(5.5),
+        records[0].get_entry(CategoryId(2)).unwrap().value
+    );
+    assert_eq!(
+        EntryStatus::Skipped,
+        records[1].get_entry(CategoryId(1)).unwrap().status
+    );
+    assert_eq!(
+        EntryStatus::Done,
+        records[1].get_entry(CategoryId(3)).unwrap().status
+    );
+    assert!(data.get_categories().unwrap()[2].hidden);
+
+    // Importing again skips the tracked days and creates no categories
+    let report = import_loop_backup(&mut data, &path, ConflictPolicy::Skip, false).unwrap();
+    assert!(report.created_categories.is_empty());
+    assert_eq!(2, report.skipped_days);
+
+    assert!(matches!(
+        import_loop_backup(
+            &mut data,
+            &dir.join("missing.db"),
+            ConflictPolicy::Skip,
+            false
+        ),
+        Err(Error::Sqlite(_))
+    ));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_dump_round_trip() {
+    let mut data = open_test_database(&["GYM", "READ", "SMOKE"]);
+    let date = |day| NaiveDate::from_ymd_opt(2000, 5, day).unwrap();
+    data.hide_category("READ").unwrap();
+    data.set_category_details(
+        "GYM",
+        &CategoryDetails {
+            display_name: Some(String::from("Gym workout")),
+            symbol: Some(String::from("🏋")),
+            color: Some(String::from("#ff8800")),
+            ..Default::default()
+        },
+    )
+    .unwrap();
+    data.set_category_schedule(
+        "GYM",
+        &Schedule::Weekdays(WeekdaySet::from_array([Weekday::Mon, Weekday::Thu])),
+    )
+    .unwrap();
+    data.set_category_polarity("SMOKE", Polarity::Avoid)
+        .unwrap();
+    data.set_target(
+        "GYM",
+        Some(Target {
+            kind: TargetKind::AtLeast,
+            count: 2,
+            days: 7,
+        }),
+    )
+    .unwrap();
+    data.update_data(&date(1), &ids([1, 2])).unwrap();
+    data.update_data(&date(2), &[]).unwrap();
+    data.set_value(&date(3), CategoryId(2), Some(12.5)).unwrap();
+    data.set_status(&date(3), CategoryId(1), Some(EntryStatus::Partial))
+        .unwrap();
+    data.set_note(&date(3), "tired").unwrap();
+    data.set_note(&date(9), "untracked").unwrap();
+    data.set_excused(None, &date(4), &date(5), true).unwrap();
+    data.set_excused(Some(CategoryId(3)), &date(6), &date(6), true)
+        .unwrap();
+
+    let dump = data.dump().unwrap();
+    assert_eq!(3, dump.categories.len());
+    assert!(dump.categories[1].category.hidden);
+    assert_eq!(3, dump.days.len());
+    assert_eq!(2, dump.notes.len());
+    assert_eq!(2, dump.excused_ranges.len());
+
+    // The restored datafile has the same content, down to the creation times
+    let json = serde_json::to_string(&dump).unwrap();
+    let mut restored = open_test_database(&[]);
+    restored
+        .restore_dump(&serde_json::from_str(&json).unwrap())
+        .unwrap();
+    let restored_dump = restored.dump().unwrap();
+    assert_eq!(dump, restored_dump);
+    assert_eq!(json, serde_json::to_string(&restored_dump).unwrap());
+    assert_eq!(
+        Some(String::from("untracked")),
+        restored.get_note(&date(9)).unwrap()
+    );
+
+    // Restoring replaces the previous content
+    restored.add_category("NEW").unwrap();
+    restored.restore_dump(&dump).unwrap();
+    assert_eq!(dump, restored.dump().unwrap());
+
+    // Dumps of newer versions are refused
+    let newer = DatafileDump {
+        version: migration::latest_version() + 1,
+        ..dump
+    };
+    assert!(matches!(
+        restored.restore_dump(&newer),
+        Err(Error::UnsupportedVersion { .. })
+    ));
+}
+
+#[test]
+fn test_concurrent_access() {
+    let dir = std::env::temp_dir().join(format!("genee_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("diary.db");
+    create_new_datafile(&path, &[String::from("PNO")], None).unwrap();
+    let day = NaiveDate::from_ymd_opt(2000, 5, 1).unwrap();
+
+    // A second instance cannot open the datafile for writing, but it can read it
+    let mut data = open_datafile(&path, None).unwrap();
+    assert!(!data.is_read_only());
+    assert!(matches!(
+        open_datafile(&path, None),
+        Err(Error::Locked { holder: Some(_) })
+    ));
+    data.update_data(&day, &ids([1])).unwrap();
+    let read_only = open_datafile_read_only(&path, None).unwrap();
+    assert!(read_only.is_read_only());
+    assert_eq!(Some(ids([1])), read_only.get_row(&day).unwrap());
+    assert_eq!(
+        1,
+        read_only
+            .calculate_data_counts_per_iter(&[(day, day)])
+            .unwrap()[0][0]
+            .count
+    );
+
+    // Edits are refused in read-only mode
+    let mut read_only = read_only;
+    assert!(read_only.set_note(&day, "note").is_err());
+    assert_eq!(None, read_only.get_note(&day).unwrap());
+
+    // The lock is released when the datafile is closed
+    drop(data);
+    open_datafile(&path, None).unwrap();
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_backups() {
+    let dir = std::env::temp_dir().join(format!("genee_backup_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("diary.db");
+    create_new_datafile(&path, &[String::from("PNO")], None).unwrap();
+    let policy = BackupPolicy {
+        directory: get_default_backup_dir(&path),
+        count: 2,
+    };
+    let day = NaiveDate::from_ymd_opt(2000, 5, 1).unwrap();
+
+    // Only the newest backups are kept
+    let mut data = open_datafile(&path, None).unwrap();
+    assert!(list_backups(&path, &policy).unwrap().is_empty());
+    let before_edit = data.create_backup(&path, &policy).unwrap();
+    std::thread::sleep(Duration::from_millis(2));
+    data.update_data(&day, &ids([1])).unwrap();
+    let after_edit = data.create_backup(&path, &policy).unwrap();
+    std::thread::sleep(Duration::from_millis(2));
+    let newest = data.create_backup(&path, &policy).unwrap();
+    let backups = list_backups(&path, &policy).unwrap();
+    assert_eq!(vec![newest, after_edit.clone()], backups);
+    assert!(!before_edit.path.exists());
+
+    // The datafile cannot be restored while it is open for writing
+    assert!(matches!(
+        restore_backup(&path, &policy, &after_edit.id, None, None),
+        Err(Error::Locked { .. })
+    ));
+    data.update_data(&day, &ids([])).unwrap();
+    drop(data);
+
+    // A corrupted backup is refused, then a valid one replaces the datafile
+    std::fs::write(&before_edit.path, b"not a datafile").unwrap();
+    assert!(matches!(
+        restore_backup(&path, &policy, &before_edit.id, None, None),
+        Err(Error::InvalidBackup { .. })
+    ));
+    assert!(matches!(
+        restore_backup(&path, &policy, "missing", None, None),
+        Err(Error::BackupNotFound(_))
+    ));
+    std::thread::sleep(Duration::from_millis(2));
+    restore_backup(&path, &policy, &after_edit.id, None, None).unwrap();
+    let data = open_datafile(&path, None).unwrap();
+    assert_eq!(Some(ids([1])), data.get_row(&day).unwrap());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn test_encryption() {
+    let dir = std::env::temp_dir().join(format!("genee_encryption_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("diary.db");
+    create_new_datafile(&path, &[String::from("PNO")], Some("secret")).unwrap();
+    assert!(is_encrypted(&path).unwrap());
+    let day = NaiveDate::from_ymd_opt(2000, 5, 1).unwrap();
+
+    // The passphrase is required to open the datafile
+    assert!(matches!(
+        open_datafile(&path, None),
+        Err(Error::PassphraseRequired)
+    ));
+    assert!(matches!(
+        open_datafile(&path, Some("wrong")),
+        Err(Error::WrongPassphrase)
+    ));
+    let mut data = open_datafile(&path, Some("secret")).unwrap();
+    data.update_data(&day, &ids([1])).unwrap();
+
+    // The backups are encrypted with the same passphrase
+    let policy = BackupPolicy {
+        directory: get_default_backup_dir(&path),
+        count: 2,
+    };
+    let backup = data.create_backup(&path, &policy).unwrap();
+    assert!(is_encrypted(&backup.path).unwrap());
+    drop(data);
+
+    // Changing the passphrase, then removing the encryption
+    rekey(&path, Some("secret"), Some("other")).unwrap();
+    assert!(open_datafile_read_only(&path, Some("secret")).is_err());
+    let data = open_datafile_read_only(&path, Some("other")).unwrap();
+    assert_eq!(Some(ids([1])), data.get_row(&day).unwrap());
+    drop(data);
+    rekey(&path, Some("other"), None).unwrap();
+    assert!(!is_encrypted(&path).unwrap());
+    let data = open_datafile(&path, None).unwrap();
+    assert_eq!(Some(ids([1])), data.get_row(&day).unwrap());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "encryption")]
+#[test]
+fn test_restore_backup_after_rekey() {
+    let dir = std::env::temp_dir().join(format!("genee_rekey_restore_test_{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("diary.db");
+    create_new_datafile(&path, &[String::from("PNO")], None).unwrap();
+    let policy = BackupPolicy {
+        directory: get_default_backup_dir(&path),
+        count: 5,
+    };
+    let day = NaiveDate::from_ymd_opt(2000, 5, 1).unwrap();
+
+    // One backup before the encryption, another one before changing the passphrase
+    let mut data = open_datafile(&path, None).unwrap();
+    data.update_data(&day, &ids([1])).unwrap();
+    let unencrypted = data.create_backup(&path, &policy).unwrap();
+    drop(data);
+    rekey(&path, None, Some("secret")).unwrap();
+    let mut data = open_datafile(&path, Some("secret")).unwrap();
+    data.update_data(&day, &ids([])).unwrap();
+    std::thread::sleep(Duration::from_millis(2));
+    let encrypted = data.create_backup(&path, &policy).unwrap();
+    drop(data);
+    rekey(&path, Some("secret"), Some("other")).unwrap();
+
+    // The backups are unlocked with their own passphrase, the datafile keeps its passphrase
+    assert!(matches!(
+        restore_backup(&path, &policy, &encrypted.id, Some("other"), Some("other")),
+        Err(Error::WrongBackupPassphrase(_))
+    ));
+    std::thread::sleep(Duration::from_millis(2));
+    restore_backup(&path, &policy, &encrypted.id, Some("other"), Some("secret")).unwrap();
+    let data = open_datafile_read_only(&path, Some("other")).unwrap();
+    assert_eq!(Some(ids([])), data.get_row(&day).unwrap());
+    drop(data);
+    std::thread::sleep(Duration::from_millis(2));
+    restore_backup(&path, &policy, &unencrypted.id, Some("other"), None).unwrap();
+    let data = open_datafile_read_only(&path, Some("other")).unwrap();
+    assert_eq!(Some(ids([1])), data.get_row(&day).unwrap());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_memory_store() {
+    let date = |day| NaiveDate::from_ymd_opt(2000, 5, day).unwrap();
+    let mut sqlite = open_test_database(&["GYM", "READ", "SMOKE", "OLD"]);
+    let schedule = Schedule::EveryNDays {
+        days: 2,
+        anchor: date(1),
+    };
+    sqlite.set_category_schedule("GYM", &schedule).unwrap();
+    sqlite
+        .set_category_polarity("SMOKE", Polarity::Avoid)
+        .unwrap();
+    sqlite.hide_category("OLD").unwrap();
+    let mut memory = DiaryDataMemory::from_categories(sqlite.get_categories().unwrap());
+
+    // The same edits on both stores give the same data and statistics
+    for store in [&mut sqlite as &mut dyn DiaryStore, &mut memory] {
+        store.update_data(&date(1), &ids([1, 2])).unwrap();
+        store.update_data(&date(2), &ids([2, 3, 4])).unwrap();
+        store.update_data(&date(3), &ids([1, 2])).unwrap();
+        store
+            .set_status(&date(3), CategoryId(2), Some(EntryStatus::Partial))
+            .unwrap();
+        store.set_value(&date(3), CategoryId(1), Some(2.5)).unwrap();
+        store.update_data(&date(3), &ids([1, 2, 3])).unwrap();
+        store
+            .set_status(&date(5), CategoryId(1), Some(EntryStatus::Skipped))
+            .unwrap();
+        store.update_data(&date(6), &ids([])).unwrap();
+        store.set_value(&date(7), CategoryId(2), Some(1.0)).unwrap();
+        store
+            .update_entries_batch(&[(
+                date(9),
+                vec![
+                    DayEntry {
+                        category_id: CategoryId(2),
+                        status: EntryStatus::Skipped,
+                        value: None,
+                    },
+                    DayEntry {
+                        category_id: CategoryId(3),
+                        status: EntryStatus::Done,
+                        value: Some(4.0),
+                    },
+                ],
+            )])
+            .unwrap();
+        store.set_note(&date(7), "note").unwrap();
+        store.set_excused(None, &date(4), &date(4), true).unwrap();
+        store
+            .set_excused(Some(CategoryId(1)), &date(8), &date(10), true)
+            .unwrap();
+        store
+            .set_excused(Some(CategoryId(1)), &date(9), &date(9), false)
+            .unwrap();
+        assert!(store.update_data(&date(8), &ids([5])).is_err());
+    }
+    let sorted = |mut items: Vec<(Vec<CategoryId>, usize)>| {
+        items.sort();
+        items
+    };
+    let ranges = get_date_ranges(&date(10), 3, 4);
+    assert_eq!(sqlite.get_header().unwrap(), memory.get_header().unwrap());
+    assert_eq!(
+        sqlite.get_days(&date(1), &date(10)).unwrap(),
+        memory.get_days(&date(1), &date(10)).unwrap()
+    );
+    assert_eq!(
+        sqlite.get_statuses(&date(1), &date(10)).unwrap(),
+        memory.get_statuses(&date(1), &date(10)).unwrap()
+    );
+    assert_eq!(
+        sqlite.get_value(&date(3), CategoryId(1)).unwrap(),
+        memory.get_value(&date(3), CategoryId(1)).unwrap()
+    );
+    assert_eq!(
+        sqlite.get_note(&date(7)).unwrap(),
+        memory.get_note(&date(7)).unwrap()
+    );
+    assert_eq!(
+        sqlite.get_excused_ranges().unwrap(),
+        memory.get_excused_ranges().unwrap()
+    );
+    assert_eq!(
+        sqlite.get_missing_dates(&None, &date(10)).unwrap(),
+        memory.get_missing_dates(&None, &date(10)).unwrap()
+    );
+    assert_eq!(
+        sqlite.calculate_data_counts_per_iter(&ranges).unwrap(),
+        memory.calculate_data_counts_per_iter(&ranges).unwrap()
+    );
+    assert_eq!(
+        sorted(
+            sqlite
+                .get_most_frequent_daily_data(&None, &date(10), None)
+                .unwrap()
+        ),
+        sorted(
+            memory
+                .get_most_frequent_daily_data(&None, &date(10), None)
+                .unwrap()
+        )
+    );
+    for id in ids([1, 2, 3]) {
+        for until in [date(3), date(7), date(10)] {
+            assert_eq!(
+                sqlite.get_streak(id, &until).unwrap(),
+                memory.get_streak(id, &until).unwrap()
+            );
+            assert_eq!(
+                sqlite.get_days_since_last_occurrence(id, &until).unwrap(),
+                memory.get_days_since_last_occurrence(id, &until).unwrap()
+            );
+        }
+    }
+}
+
+#[test]
+fn test_day_records() {
+    let mut data = open_test_database(&["GYM", "READ"]);
+    data.set_category_schedule(
+        "GYM",
+        &Schedule::Weekdays(WeekdaySet::from_array([Weekday::Mon, Weekday::Thu])),
+    )
+    .unwrap();
+    let first = NaiveDate::from_ymd_opt(2000, 1, 1).unwrap();
+    let last = NaiveDate::from_ymd_opt(2000, 12, 31).unwrap();
+    data.update_data(&first, &ids([1])).unwrap();
+    data.set_value(&first, CategoryId(2), Some(1.5)).unwrap();
+    data.set_note(&last, "last").unwrap();
+
+    // The days are streamed in chronological order across the batches, including the untracked ones
+    let records: Vec<DayRecord> = data.iter_days(first..=last).collect::<Result<_>>().unwrap();
+    assert_eq!(366, records.len());
+    assert!(records.windows(2).all(|pair| pair[0].date < pair[1].date));
+    assert_eq!(
+        Some(vec![
+            DayEntry {
+                category_id: CategoryId(1),
+                status: EntryStatus::Done,
+                value: None,
+            },
+            DayEntry {
+                category_id: CategoryId(2),
+                status: EntryStatus::Done,
+                value: Some(1.5),
+            }
+        ]),
+        records[0].entries
+    );
+    assert_eq!(None, records[1].entries);
+    assert_eq!(Some(String::from("last")), records[365].note);
+    assert_eq!(0, data.iter_days(last..=first).count());
+
+    // Categories and records survive a round trip through serialization
+    let categories = data.get_categories().unwrap();
+    let json = serde_json::to_string(&categories).unwrap();
+    assert!(json.contains(r#""weekdays":["Mon","Thu"]"#));
+    assert_eq!(
+        categories,
+        serde_json::from_str::<Vec<Category>>(&json).unwrap()
+    );
+    let json = serde_json::to_string(&records[..2]).unwrap();
+    assert_eq!(
+        records[..2],
+        serde_json::from_str::<Vec<DayRecord>>(&json).unwrap()
+    );
+}
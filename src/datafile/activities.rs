@@ -0,0 +1,224 @@
+//! Import of the activities logged by other apps, e.g. Daylio or Habitica.
+use super::{
+    CategoryId, ConflictPolicy, DayEntry, DiaryDataSqlite, DiaryStore, EntryStatus, Error,
+    ImportReport, Result,
+    import::{apply_import, get_or_create_category},
+};
+use chrono::{DateTime, Local, NaiveDate};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Read,
+};
+
+/// Separator of the activities of a Daylio entry.
+const DAYLIO_ACTIVITY_SEPARATOR: &str = " | ";
+
+/// Named activities and the days they were done on, read from the export of another app.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ActivityLog {
+    /// Names of the activities, in the order they are listed by the app.
+    pub activities: Vec<String>,
+
+    /// Activities done on each day.
+    pub days: BTreeMap<NaiveDate, BTreeSet<String>>,
+}
+
+impl ActivityLog {
+    /// Adds an activity to the list if it is not listed yet.
+    fn add_activity(&mut self, activity: &str) {
+        if !self.activities.iter().any(|name| name == activity) {
+            self.activities.push(activity.to_string());
+        }
+    }
+
+    /// Records an activity done on a day.
+    fn add(&mut self, date: NaiveDate, activity: &str) {
+        self.add_activity(activity);
+        self.days
+            .entry(date)
+            .or_default()
+            .insert(activity.to_string());
+    }
+
+    /// Returns the number of days the activity was done on.
+    pub fn count_days(&self, activity: &str) -> usize {
+        self.days
+            .values()
+            .filter(|activities| activities.contains(activity))
+            .count()
+    }
+}
+
+/// Reads the CSV export of Daylio, which has one row per mood entry with the activities
+/// of the entry separated by `|`. Every day with an entry is tracked, even if it has no activities.
+/// The activities are listed in the order they first appear.
+pub fn read_daylio_export(reader: impl Read) -> Result<ActivityLog> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(reader);
+    let headers = reader.headers()?.clone();
+    let column = |name: &str| {
+        headers
+            .iter()
+            .position(|header| header.trim() == name)
+            .ok_or_else(|| Error::InvalidInput(format!("there is no \"{}\" column", name)))
+    };
+    let date_index = column("full_date")?;
+    let activities_index = column("activities")?;
+
+    let mut log = ActivityLog::default();
+    let mut rows = vec![];
+    for row in reader.records() {
+        let row = row?;
+        let date_cell = row.get(date_index).unwrap_or_default().trim();
+        let date = NaiveDate::parse_from_str(date_cell, "%Y-%m-%d").map_err(|_| {
+            Error::InvalidInput(format!(
+                "invalid date \"{}\" on line {}",
+                date_cell,
+                row.position().map_or(0, |position| position.line())
+            ))
+        })?;
+        rows.push((date, row));
+    }
+    // The newest entries come first in the export
+    rows.sort_by_key(|(date, _)| *date);
+    for (date, row) in rows {
+        log.days.entry(date).or_default();
+        let activities = row.get(activities_index).unwrap_or_default();
+        for activity in activities.split(DAYLIO_ACTIVITY_SEPARATOR) {
+            let activity = activity.trim();
+            if !activity.is_empty() {
+                log.add(date, activity);
+            }
+        }
+    }
+    Ok(log)
+}
+
+#[derive(Deserialize)]
+struct HabiticaExport {
+    tasks: HabiticaTasks,
+}
+
+#[derive(Deserialize)]
+struct HabiticaTasks {
+    #[serde(default)]
+    dailys: Vec<HabiticaDaily>,
+}
+
+#[derive(Deserialize)]
+struct HabiticaDaily {
+    text: String,
+    #[serde(default)]
+    history: Vec<HabiticaHistoryEntry>,
+}
+
+#[derive(Deserialize)]
+struct HabiticaHistoryEntry {
+    /// Time of the entry, either as a timestamp in milliseconds or as an RFC 3339 string.
+    date: serde_json::Value,
+    value: f64,
+    completed: Option<bool>,
+}
+
+/// Reads the JSON data export of Habitica and returns the completions of the dailies.
+/// Every day with a history entry is tracked, even if no daily was completed on it.
+/// Habitica records the dailies when the next day starts, so each history entry is assigned
+/// to the day before it. The old entries without completion status are considered completed
+/// when the value of the daily increased.
+pub fn read_habitica_export(reader: impl Read) -> Result<ActivityLog> {
+    let export: HabiticaExport = serde_json::from_reader(reader)
+        .map_err(|error| Error::InvalidInput(format!("invalid Habitica export: {}", error)))?;
+    let mut log = ActivityLog::default();
+    for daily in export.tasks.dailys {
+        let name = daily.text.trim();
+        if name.is_empty() {
+            continue;
+        }
+        log.add_activity(name);
+        let mut previous_value = None;
+        for entry in daily.history {
+            let completed = entry
+                .completed
+                .unwrap_or(previous_value.is_some_and(|previous| entry.value > previous));
+            previous_value = Some(entry.value);
+            let date = parse_habitica_date(&entry.date)?;
+            let date = date.pred_opt().unwrap_or(date);
+            if completed {
+                log.add(date, name);
+            } else {
+                log.days.entry(date).or_default();
+            }
+        }
+    }
+    Ok(log)
+}
+
+fn parse_habitica_date(value: &serde_json::Value) -> Result<NaiveDate> {
+    let time = match value {
+        serde_json::Value::Number(number) => number
+            .as_i64()
+            .and_then(DateTime::from_timestamp_millis)
+            .map(|time| time.with_timezone(&Local)),
+        serde_json::Value::String(text) => DateTime::parse_from_rfc3339(text)
+            .ok()
+            .map(|time| time.with_timezone(&Local)),
+        _ => None,
+    };
+    time.map(|time| time.date_naive())
+        .ok_or_else(|| Error::InvalidInput(format!("invalid Habitica date {}", value)))
+}
+
+/// Imports the activities as entries of the categories they are mapped to, given as
+/// `(activity, category name)` pairs. The categories that do not exist are created,
+/// and the activities that are not mapped are ignored.
+/// Nothing is written in a dry run, but the returned report describes what would be imported.
+pub fn import_activities(
+    datafile: &mut DiaryDataSqlite,
+    log: &ActivityLog,
+    mapping: &[(String, String)],
+    conflict_policy: ConflictPolicy,
+    dry_run: bool,
+) -> Result<ImportReport> {
+    let mut report = ImportReport::default();
+    let mut categories = datafile.get_categories()?;
+    let mut category_ids = BTreeMap::new();
+    for activity in &log.activities {
+        let Some((_, name)) = mapping.iter().find(|(mapped, _)| mapped == activity) else {
+            report.ignored_columns.push(activity.clone());
+            continue;
+        };
+        let category_id =
+            get_or_create_category(datafile, &mut categories, name, false, dry_run, &mut report)?;
+        report.mapped_columns.push((activity.clone(), category_id));
+        category_ids.insert(activity.as_str(), category_id);
+    }
+
+    let imported = log
+        .days
+        .iter()
+        .map(|(date, activities)| {
+            let entries = activities
+                .iter()
+                .filter_map(|activity| category_ids.get(activity.as_str()))
+                .map(|category_id| {
+                    let entry = DayEntry {
+                        category_id: *category_id,
+                        status: EntryStatus::Done,
+                        value: None,
+                    };
+                    (*category_id, entry)
+                });
+            (*date, entries.collect())
+        })
+        .collect();
+    let imported_categories: BTreeSet<CategoryId> = category_ids.values().copied().collect();
+    apply_import(
+        datafile,
+        imported,
+        &imported_categories,
+        conflict_policy,
+        dry_run,
+        &mut report,
+    )?;
+    Ok(report)
+}
@@ -0,0 +1,212 @@
+//! Rotating, timestamped backups of SQLite datafiles.
+use super::{
+    BUSY_TIMEOUT, DiaryDataSqlite, Error, Result, encryption, get_backup_path, lock_datafile,
+    migration,
+};
+use chrono::{Local, NaiveDateTime};
+use rusqlite::{Connection, OpenFlags, backup};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Format of the backup identifiers, which sort in chronological order.
+const BACKUP_ID_FORMAT: &str = "%Y%m%d-%H%M%S-%3f";
+
+/// Where the backups of a datafile are kept and how many of them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackupPolicy {
+    /// Directory of the backup files.
+    pub directory: PathBuf,
+
+    /// Number of backups kept, the oldest ones are deleted beyond it.
+    /// Zero disables the automatic backups when the datafile is opened.
+    pub count: usize,
+}
+
+/// A backup of a datafile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Backup {
+    /// Identifier of the backup, derived from its creation time.
+    pub id: String,
+
+    /// Path of the backup file.
+    pub path: PathBuf,
+
+    /// Local time the backup was created at.
+    pub created_at: NaiveDateTime,
+
+    /// Size of the backup file in bytes.
+    pub size: u64,
+}
+
+impl DiaryDataSqlite {
+    /// Writes a new timestamped backup of the datafile according to the policy,
+    /// then deletes the oldest backups beyond the retained count.
+    /// The new backup is always retained.
+    pub fn create_backup(&self, datafile_path: &Path, policy: &BackupPolicy) -> Result<Backup> {
+        write_backup(&self.connection, self.key.as_deref(), datafile_path, policy)
+    }
+}
+
+/// Returns the backups of the datafile, the newest first.
+pub fn list_backups(datafile_path: &Path, policy: &BackupPolicy) -> Result<Vec<Backup>> {
+    if !policy.directory.exists() {
+        return Ok(Vec::new());
+    }
+    let mut backups = Vec::new();
+    let read_error = || {
+        format!(
+            "Could not list the backups in {}",
+            policy.directory.display()
+        )
+    };
+    for dir_entry in fs::read_dir(&policy.directory).map_err(Error::io(read_error()))? {
+        let file_name = dir_entry.map_err(Error::io(read_error()))?.file_name();
+        let Some(id) = file_name
+            .to_str()
+            .and_then(|name| name.strip_prefix(&get_backup_file_prefix(datafile_path)))
+            .and_then(|name| name.strip_suffix(".bak"))
+        else {
+            continue;
+        };
+        if let Some(backup) = read_backup(datafile_path, policy, id) {
+            backups.push(backup);
+        }
+    }
+    backups.sort_by(|a, b| b.id.cmp(&a.id));
+    Ok(backups)
+}
+
+/// Replaces the datafile with the specified backup, after checking the integrity of the backup.
+/// The datafile is backed up itself before being replaced, so the restore can be undone.
+/// Fails if another running instance has the datafile open for writing.
+/// The backup is unlocked with `backup_key`, which differs from the passphrase of the datafile
+/// if the backup was made before a rekey, and is not used if the backup is not encrypted.
+/// The restored datafile keeps its current passphrase.
+pub fn restore_backup(
+    datafile_path: &Path,
+    policy: &BackupPolicy,
+    id: &str,
+    key: Option<&str>,
+    backup_key: Option<&str>,
+) -> Result<Backup> {
+    let backup = read_backup(datafile_path, policy, id)
+        .ok_or_else(|| Error::BackupNotFound(String::from(id)))?;
+    let backup_key = backup_key.filter(|_| encryption::is_encrypted(&backup.path).unwrap_or(true));
+    let backup_connection =
+        encryption::connect(&backup.path, OpenFlags::SQLITE_OPEN_READ_ONLY, backup_key).map_err(
+            |error| match error {
+                Error::PassphraseRequired | Error::WrongPassphrase => {
+                    Error::WrongBackupPassphrase(String::from(id))
+                }
+                error => error,
+            },
+        )?;
+    check_backup(&backup_connection).map_err(|error| Error::InvalidBackup {
+        id: String::from(id),
+        source: Box::new(error),
+    })?;
+
+    let _lock = lock_datafile(datafile_path)?;
+    let mut connection = encryption::connect(datafile_path, OpenFlags::default(), key)?;
+    connection.busy_timeout(BUSY_TIMEOUT)?;
+    write_backup(&connection, key, datafile_path, policy)?;
+    if backup_key == key {
+        copy_database(&backup_connection, &mut connection)?;
+    } else {
+        // Pages cannot be copied between databases with different passphrases,
+        // so the backup is exported with the passphrase of the datafile first
+        let exported_path = get_backup_path(datafile_path, "restore");
+        encryption::export(&backup.path, backup_key, &exported_path, key)?;
+        let copied = encryption::connect(&exported_path, OpenFlags::SQLITE_OPEN_READ_ONLY, key)
+            .and_then(|exported| copy_database(&exported, &mut connection));
+        fs::remove_file(&exported_path)
+            .map_err(Error::io("Could not remove the exported backup"))?;
+        copied?;
+    }
+    Ok(backup)
+}
+
+/// Returns the default directory of the backups, next to the datafile.
+pub fn get_default_backup_dir(datafile_path: &Path) -> PathBuf {
+    get_backup_path(datafile_path, "backups")
+}
+
+/// Fails if the backup is not a consistent datafile that can be opened by this version of the program.
+fn check_backup(connection: &Connection) -> Result<()> {
+    let integrity: String = connection.query_row("PRAGMA integrity_check", [], |row| row.get(0))?;
+    if integrity != "ok" {
+        return Err(Error::CorruptData(integrity));
+    }
+    let violations: usize =
+        connection.query_row("SELECT COUNT(*) FROM pragma_foreign_key_check", [], |row| {
+            row.get(0)
+        })?;
+    Error::check_violations("Checking the backup", violations)?;
+    migration::get_pending(migration::get_version(connection)?)?;
+    Ok(())
+}
+
+fn copy_database(source: &Connection, destination: &mut Connection) -> Result<()> {
+    backup::Backup::new(source, destination)?.run_to_completion(
+        100,
+        std::time::Duration::default(),
+        None,
+    )?;
+    Ok(())
+}
+
+fn write_backup(
+    connection: &Connection,
+    key: Option<&str>,
+    datafile_path: &Path,
+    policy: &BackupPolicy,
+) -> Result<Backup> {
+    fs::create_dir_all(&policy.directory)
+        .map_err(Error::io("Could not create the directory of the backups"))?;
+    let id = Local::now().format(BACKUP_ID_FORMAT).to_string();
+    let path = get_backup_file_path(datafile_path, policy, &id);
+    {
+        let mut backup_connection = encryption::connect(&path, OpenFlags::default(), key)?;
+        copy_database(connection, &mut backup_connection)?;
+        // The backup is a single self-contained file, regardless of the journal of the datafile
+        backup_connection.execute_batch("PRAGMA journal_mode=DELETE;")?;
+    }
+    for outdated in list_backups(datafile_path, policy)?
+        .iter()
+        .skip(policy.count.max(1))
+    {
+        fs::remove_file(&outdated.path).map_err(Error::io(format!(
+            "Could not delete backup {}",
+            outdated.path.display()
+        )))?;
+    }
+    read_backup(datafile_path, policy, &id).ok_or(Error::BackupNotFound(id))
+}
+
+/// Returns the backup with the identifier, or `None` if the identifier is invalid or there is no such backup.
+fn read_backup(datafile_path: &Path, policy: &BackupPolicy, id: &str) -> Option<Backup> {
+    let created_at = NaiveDateTime::parse_from_str(id, BACKUP_ID_FORMAT).ok()?;
+    let path = get_backup_file_path(datafile_path, policy, id);
+    let size = fs::metadata(&path).ok()?.len();
+    Some(Backup {
+        id: String::from(id),
+        path,
+        created_at,
+        size,
+    })
+}
+
+fn get_backup_file_prefix(datafile_path: &Path) -> String {
+    let file_name = datafile_path.file_name().unwrap_or_default();
+    format!("{}.", file_name.to_string_lossy())
+}
+
+fn get_backup_file_path(datafile_path: &Path, policy: &BackupPolicy, id: &str) -> PathBuf {
+    policy.directory.join(format!(
+        "{}{}.bak",
+        get_backup_file_prefix(datafile_path),
+        id
+    ))
+}
@@ -0,0 +1,172 @@
+//! Integrity check and repair of SQLite datafiles.
+use super::{
+    Backup, BackupPolicy, Category, DiaryDataSqlite, DiaryStore, Error, Result, date_to_day,
+    day_to_date, migration,
+};
+use chrono::NaiveDate;
+use rusqlite::params;
+use std::path::Path;
+
+/// Columns storing dates as day numbers.
+const DATE_COLUMNS: &[(&str, &str)] = &[
+    ("DateEntry", "date"),
+    ("EntryToCategories", "date"),
+    ("DateNote", "date"),
+    ("ExcusedRange", "from_date"),
+    ("ExcusedRange", "until_date"),
+    ("Category", "schedule_anchor"),
+];
+
+/// Findings of the integrity check of a datafile.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IntegrityReport {
+    /// Schema version of the datafile.
+    pub version: usize,
+
+    /// Earliest and latest tracked days, if any.
+    pub date_range: Option<(NaiveDate, NaiveDate)>,
+
+    /// All categories, including the hidden ones.
+    pub categories: Vec<Category>,
+
+    /// Number of entries referring to a missing day or category.
+    pub orphan_entries: usize,
+
+    /// Number of excused ranges referring to a missing category.
+    pub orphan_excused_ranges: usize,
+
+    /// Category names used by more than one category.
+    pub duplicate_names: Vec<String>,
+
+    /// Number of stored dates that are not valid day numbers.
+    pub invalid_dates: usize,
+}
+
+impl IntegrityReport {
+    /// Returns whether no problems were found.
+    pub fn is_healthy(&self) -> bool {
+        self.orphan_entries == 0
+            && self.orphan_excused_ranges == 0
+            && self.duplicate_names.is_empty()
+            && self.invalid_dates == 0
+    }
+}
+
+impl DiaryDataSqlite {
+    /// Checks the consistency of the datafile without modifying it.
+    pub fn check_integrity(&self) -> Result<IntegrityReport> {
+        let day_range = params![date_to_day(&NaiveDate::MIN), date_to_day(&NaiveDate::MAX)];
+        let (first_day, last_day): (Option<i64>, Option<i64>) = self.connection.query_row(
+            &format!(
+                "SELECT MIN(date), MAX(date) FROM DateEntry WHERE NOT ({})",
+                invalid_date("date")
+            ),
+            day_range,
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let date_range = first_day
+            .and_then(day_to_date)
+            .zip(last_day.and_then(day_to_date));
+        let orphan_entries = self.connection.query_row(
+            "SELECT COUNT(*) FROM EntryToCategories
+                WHERE date NOT IN (SELECT date FROM DateEntry)
+                    OR category_id NOT IN (SELECT category_id FROM Category)",
+            [],
+            |row| row.get(0),
+        )?;
+        let orphan_excused_ranges = self.connection.query_row(
+            "SELECT COUNT(*) FROM ExcusedRange
+                WHERE category_id NOTNULL AND category_id NOT IN (SELECT category_id FROM Category)",
+            [],
+            |row| row.get(0),
+        )?;
+        let mut statement = self
+            .connection
+            .prepare("SELECT name FROM Category GROUP BY name HAVING COUNT(*) > 1 ORDER BY name")?;
+        let duplicate_names = statement
+            .query_map([], |row| row.get(0))?
+            .collect::<Result<_, _>>()?;
+        let mut invalid_dates = 0;
+        for (table, column) in DATE_COLUMNS {
+            invalid_dates += self.connection.query_row(
+                &format!(
+                    "SELECT COUNT(*) FROM {table} WHERE {}",
+                    invalid_date(column)
+                ),
+                day_range,
+                |row| row.get::<usize, usize>(0),
+            )?;
+        }
+        Ok(IntegrityReport {
+            version: migration::get_version(&self.connection)?,
+            date_range,
+            categories: self.get_categories()?,
+            orphan_entries,
+            orphan_excused_ranges,
+            duplicate_names,
+            invalid_dates,
+        })
+    }
+
+    /// Repairs the problems reported by `check_integrity`:
+    /// - rows with invalid dates are removed, schedules with an invalid first day are reset,
+    /// - entries of missing days are kept by adding the days, entries of missing categories are removed,
+    /// - excused ranges of missing categories are removed,
+    /// - categories with duplicate names are renamed by appending their identifier.
+    pub fn repair(&mut self) -> Result<()> {
+        // Days referenced by foreign keys are removed, so enforcement is suspended meanwhile
+        self.connection.execute_batch("PRAGMA foreign_keys=OFF;")?;
+        let result = self.repair_internal();
+        self.connection.execute_batch("PRAGMA foreign_keys=ON;")?;
+        result
+    }
+
+    /// Backs up the datafile, then repairs it like `repair`. The backup is made and retained
+    /// even if the policy disables the automatic backups. Returns the backup.
+    pub fn repair_with_backup(
+        &mut self,
+        datafile_path: &Path,
+        policy: &BackupPolicy,
+    ) -> Result<Backup> {
+        let backup = self.create_backup(datafile_path, policy)?;
+        self.repair()?;
+        Ok(backup)
+    }
+
+    fn repair_internal(&mut self) -> Result<()> {
+        let transaction = self.connection.transaction()?;
+        let day_range = params![date_to_day(&NaiveDate::MIN), date_to_day(&NaiveDate::MAX)];
+        for (table, column) in DATE_COLUMNS {
+            let statement = if *table == "Category" {
+                format!(
+                    "UPDATE Category SET schedule_every=NULL, {column}=NULL WHERE {}",
+                    invalid_date(column)
+                )
+            } else {
+                format!("DELETE FROM {table} WHERE {}", invalid_date(column))
+            };
+            transaction.execute(&statement, day_range)?;
+        }
+        transaction.execute_batch(
+            "INSERT OR IGNORE INTO DateEntry (date, created_at)
+                SELECT DISTINCT date, unixepoch() FROM EntryToCategories;
+            DELETE FROM EntryToCategories WHERE category_id NOT IN (SELECT category_id FROM Category);
+            DELETE FROM ExcusedRange
+                WHERE category_id NOTNULL AND category_id NOT IN (SELECT category_id FROM Category);
+            UPDATE Category SET name=name || '-' || category_id
+                WHERE category_id NOT IN (SELECT MIN(category_id) FROM Category GROUP BY name);",
+        )?;
+        let violations: usize =
+            transaction.query_row("SELECT COUNT(*) FROM pragma_foreign_key_check", [], |row| {
+                row.get(0)
+            })?;
+        Error::check_violations("Could not repair the datafile", violations)?;
+        transaction.commit()?;
+        Ok(())
+    }
+}
+
+/// SQL condition of a non-NULL date column not holding a day number in the range `?1..=?2`.
+fn invalid_date(column: &str) -> String {
+    format!("{column} NOTNULL AND (typeof({column})<>'integer' OR {column} NOT BETWEEN ?1 AND ?2)")
+}
@@ -0,0 +1,251 @@
+//! Lossless dump of SQLite datafiles, e.g. to be exported as JSON.
+use super::{
+    Category, DayEntry, DiaryDataSqlite, DiaryStore, EntryStatus, Error, ExcusedRange, Polarity,
+    Result, Target, date_to_day, get_date, migration, schedule_to_db,
+};
+use chrono::NaiveDate;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+
+/// Complete content of a datafile.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DatafileDump {
+    /// Schema version of the datafile.
+    pub version: usize,
+
+    /// All categories including the hidden ones, in display order.
+    pub categories: Vec<CategoryDump>,
+
+    /// All tracked days in chronological order.
+    pub days: Vec<DayDump>,
+
+    /// All notes in chronological order, including the ones attached to untracked days.
+    #[serde(default)]
+    pub notes: Vec<NoteDump>,
+
+    /// All excused ranges.
+    #[serde(default)]
+    pub excused_ranges: Vec<ExcusedRange>,
+}
+
+/// A category with the properties that are only stored in the datafile.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CategoryDump {
+    #[serde(flatten)]
+    pub category: Category,
+
+    /// Creation time as a UNIX timestamp.
+    pub created_at: i64,
+
+    /// Position of the category in the display order.
+    #[serde(default)]
+    pub sort_order: i64,
+
+    /// Frequency target of the category.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub target: Option<Target>,
+}
+
+/// A tracked day with its entries.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct DayDump {
+    pub date: NaiveDate,
+
+    /// Time when the day was first tracked, as a UNIX timestamp.
+    pub created_at: i64,
+
+    /// Entries of the day, ordered by category ID.
+    #[serde(default)]
+    pub entries: Vec<DayEntry>,
+}
+
+/// A note attached to a day.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NoteDump {
+    pub date: NaiveDate,
+    pub note: String,
+
+    /// Time when the note was written, as a UNIX timestamp.
+    pub created_at: i64,
+}
+
+impl DiaryDataSqlite {
+    /// Returns the complete content of the datafile.
+    pub fn dump(&self) -> Result<DatafileDump> {
+        let targets = self.get_targets()?;
+        let mut statement = self
+            .connection
+            .prepare("SELECT created_at, sort_order FROM Category WHERE category_id=?1")?;
+        let mut categories = vec![];
+        for category in self.get_categories()? {
+            let (created_at, sort_order) =
+                statement.query_row([category.id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            categories.push(CategoryDump {
+                target: targets.get(&category.id).copied(),
+                category,
+                created_at,
+                sort_order,
+            });
+        }
+
+        let mut statement = self
+            .connection
+            .prepare("SELECT date, created_at FROM DateEntry ORDER BY date")?;
+        let mut days = statement
+            .query_map([], |row| {
+                Ok(DayDump {
+                    date: get_date(row, 0)?,
+                    created_at: row.get(1)?,
+                    entries: vec![],
+                })
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut statement = self.connection.prepare(
+            "SELECT date, category_id, status, value FROM EntryToCategories
+                ORDER BY date, category_id",
+        )?;
+        let entries = statement.query_map([], |row| {
+            Ok((
+                get_date(row, 0)?,
+                DayEntry {
+                    category_id: row.get(1)?,
+                    status: EntryStatus::from_db(row.get(2)?),
+                    value: row.get(3)?,
+                },
+            ))
+        })?;
+        // Both the days and the entries are ordered by date
+        let mut day_index = 0;
+        for entry in entries {
+            let (date, entry) = entry?;
+            while days.get(day_index).is_some_and(|day| day.date < date) {
+                day_index += 1;
+            }
+            match days.get_mut(day_index) {
+                Some(day) if day.date == date => day.entries.push(entry),
+                _ => {
+                    return Err(Error::CorruptData(format!(
+                        "entry of category {} on untracked day {}",
+                        entry.category_id, date
+                    )));
+                }
+            }
+        }
+
+        let mut statement = self
+            .connection
+            .prepare("SELECT date, note, created_at FROM DateNote ORDER BY date")?;
+        let notes = statement
+            .query_map([], |row| {
+                Ok(NoteDump {
+                    date: get_date(row, 0)?,
+                    note: row.get(1)?,
+                    created_at: row.get(2)?,
+                })
+            })?
+            .collect::<Result<_, _>>()?;
+
+        Ok(DatafileDump {
+            version: migration::get_version(&self.connection)?,
+            categories,
+            days,
+            notes,
+            excused_ranges: self.get_excused_ranges()?,
+        })
+    }
+
+    /// Replaces the complete content of the datafile with a dump.
+    /// Fails if the dump was made by a newer version of the program.
+    pub fn restore_dump(&mut self, dump: &DatafileDump) -> Result<()> {
+        let latest = migration::latest_version();
+        if dump.version > latest {
+            return Err(Error::UnsupportedVersion {
+                version: dump.version,
+                latest,
+            });
+        }
+        let transaction = self.connection.transaction()?;
+        transaction.execute_batch(
+            "DELETE FROM EntryToCategories;
+            DELETE FROM ExcusedRange;
+            DELETE FROM DateNote;
+            DELETE FROM DateEntry;
+            DELETE FROM Category;",
+        )?;
+        {
+            let mut statement = transaction.prepare(
+                "INSERT INTO Category (category_id, name, created_at, hidden, sort_order,
+                        target_kind, target_count, target_days,
+                        display_name, description, symbol, color, avoid,
+                        schedule_weekdays, schedule_every, schedule_anchor)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)",
+            )?;
+            for CategoryDump {
+                category,
+                created_at,
+                sort_order,
+                target,
+            } in &dump.categories
+            {
+                let (weekdays, every, anchor) = schedule_to_db(&category.schedule);
+                let details = &category.details;
+                statement.execute(params![
+                    category.id,
+                    category.name,
+                    created_at,
+                    category.hidden,
+                    sort_order,
+                    target.map(|target| target.kind.to_db()),
+                    target.map(|target| target.count),
+                    target.map(|target| target.days),
+                    details.display_name,
+                    details.description,
+                    details.symbol,
+                    details.color,
+                    category.polarity == Polarity::Avoid,
+                    weekdays,
+                    every,
+                    anchor
+                ])?;
+            }
+
+            let mut day_statement =
+                transaction.prepare("INSERT INTO DateEntry (date, created_at) VALUES (?1, ?2)")?;
+            let mut entry_statement = transaction.prepare(
+                "INSERT INTO EntryToCategories (date, category_id, status, value)
+                    VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for day in &dump.days {
+                let date = date_to_day(&day.date);
+                day_statement.execute(params![date, day.created_at])?;
+                for entry in &day.entries {
+                    entry_statement.execute(params![
+                        date,
+                        entry.category_id,
+                        entry.status.to_db(),
+                        entry.value
+                    ])?;
+                }
+            }
+
+            let mut statement = transaction
+                .prepare("INSERT INTO DateNote (date, note, created_at) VALUES (?1, ?2, ?3)")?;
+            for note in &dump.notes {
+                statement.execute(params![date_to_day(&note.date), note.note, note.created_at])?;
+            }
+
+            let mut statement = transaction.prepare(
+                "INSERT INTO ExcusedRange (category_id, from_date, until_date) VALUES (?1, ?2, ?3)",
+            )?;
+            for range in &dump.excused_ranges {
+                statement.execute(params![
+                    range.category_id,
+                    date_to_day(&range.from),
+                    date_to_day(&range.until)
+                ])?;
+            }
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+}
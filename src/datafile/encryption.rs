@@ -0,0 +1,89 @@
+//! Passphrase protection of SQLite datafiles, available with the `encryption` feature.
+use super::{BUSY_TIMEOUT, Error, Result, get_backup_path, lock_datafile};
+use rusqlite::{Connection, OpenFlags, params};
+use std::{fs, io::Read, path::Path};
+
+/// Header of the unencrypted SQLite database files.
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+/// Returns whether the datafile is encrypted. Missing and empty files are not encrypted.
+pub fn is_encrypted(path: &Path) -> Result<bool> {
+    if !path.exists() {
+        return Ok(false);
+    }
+    let mut header = Vec::with_capacity(SQLITE_HEADER.len());
+    fs::File::open(path)
+        .and_then(|file| {
+            file.take(SQLITE_HEADER.len() as u64)
+                .read_to_end(&mut header)
+        })
+        .map_err(Error::io(format!("Could not read {}", path.display())))?;
+    Ok(header.len() == SQLITE_HEADER.len() && header != SQLITE_HEADER)
+}
+
+/// Opens an SQLite database, unlocking it with the passphrase if there is any.
+/// Fails if the passphrase is wrong, or if it is missing for an encrypted database.
+pub(super) fn connect(path: &Path, flags: OpenFlags, key: Option<&str>) -> Result<Connection> {
+    let Some(key) = key else {
+        if is_encrypted(path)? {
+            return Err(Error::PassphraseRequired);
+        }
+        return Ok(Connection::open_with_flags(path, flags)?);
+    };
+    check_encryption_support()?;
+    let connection = Connection::open_with_flags(path, flags)?;
+    // A wrong passphrase is reported as an error, not logged
+    connection.pragma_update(None, "cipher_log_level", "NONE")?;
+    connection.pragma_update(None, "key", key)?;
+    // The passphrase is only checked when the database is first read
+    connection
+        .query_row("SELECT COUNT(*) FROM sqlite_master", [], |_| Ok(()))
+        .map_err(|_| Error::WrongPassphrase)?;
+    Ok(connection)
+}
+
+/// Changes the passphrase of the datafile. Without a passphrase, the datafile is encrypted with
+/// the new one; without a new passphrase, the encryption is removed.
+/// Fails if another running instance has the datafile open for writing.
+pub fn rekey(path: &Path, key: Option<&str>, new_key: Option<&str>) -> Result<()> {
+    check_encryption_support()?;
+    let _lock = lock_datafile(path)?;
+    let rekeyed_path = get_backup_path(path, "rekey");
+    // The datafile is exported to a new file, as the encryption cannot be added to
+    // or removed from a database in place
+    export(path, key, &rekeyed_path, new_key)?;
+    fs::rename(&rekeyed_path, path).map_err(Error::io("Could not replace the datafile"))?;
+    Ok(())
+}
+
+/// Copies the database to a new file encrypted with `new_key`, or unencrypted without it.
+pub(super) fn export(
+    path: &Path,
+    key: Option<&str>,
+    exported_path: &Path,
+    new_key: Option<&str>,
+) -> Result<()> {
+    check_encryption_support()?;
+    if exported_path.exists() {
+        fs::remove_file(exported_path).map_err(Error::io(format!(
+            "Could not remove the leftover {}",
+            exported_path.display()
+        )))?;
+    }
+    let connection = connect(path, OpenFlags::default(), key)?;
+    connection.busy_timeout(BUSY_TIMEOUT)?;
+    connection.execute(
+        "ATTACH DATABASE ?1 AS exported KEY ?2",
+        params![exported_path.to_string_lossy(), new_key.unwrap_or_default()],
+    )?;
+    connection.query_row("SELECT sqlcipher_export('exported')", [], |_| Ok(()))?;
+    connection.execute_batch("DETACH DATABASE exported;")?;
+    Ok(())
+}
+
+fn check_encryption_support() -> Result<()> {
+    if !cfg!(feature = "encryption") {
+        return Err(Error::EncryptionUnsupported);
+    }
+    Ok(())
+}
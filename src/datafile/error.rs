@@ -0,0 +1,184 @@
+//! Errors reported by the handling of habit databases.
+use super::CategoryId;
+use std::{fmt::Display, io};
+
+/// Result of the operations on habit databases.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// Error of an operation on a habit database.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// A file could not be read or written.
+    Io {
+        /// What was being done with the file.
+        context: String,
+        source: io::Error,
+    },
+
+    /// The database engine reported an error.
+    Sqlite(rusqlite::Error),
+
+    /// A delimited text file could not be read or written.
+    Csv(csv::Error),
+
+    /// No day was tracked in the diary yet.
+    EmptyDiary,
+
+    /// The datafile holds a value that cannot be interpreted.
+    CorruptData(String),
+
+    /// Foreign key constraints of the datafile are violated.
+    ForeignKeyViolation {
+        /// What was being done when the violations were found.
+        context: String,
+        violations: usize,
+    },
+
+    /// The datafile was written by a newer version of the program.
+    UnsupportedVersion { version: usize, latest: usize },
+
+    /// The datafile needs to be updated to the latest schema version,
+    /// which is not possible because it is opened in read-only mode.
+    MigrationRequired { version: usize, latest: usize },
+
+    /// The datafile is opened in read-only mode, so it cannot be modified.
+    ReadOnly,
+
+    /// Another running instance has the datafile open for writing.
+    Locked {
+        /// Process identifier of the instance holding the datafile, if known.
+        holder: Option<String>,
+    },
+
+    /// The datafile is encrypted, but no passphrase was provided.
+    PassphraseRequired,
+
+    /// The passphrase is wrong, or the file is not a datafile.
+    WrongPassphrase,
+
+    /// The passphrase of the backup with the identifier is wrong or missing.
+    WrongBackupPassphrase(String),
+
+    /// The program was built without support for encrypted datafiles.
+    EncryptionUnsupported,
+
+    /// The category does not exist.
+    NonExistingCategory(CategoryId),
+
+    /// The imported data is malformed.
+    InvalidInput(String),
+
+    /// There is no backup with the identifier.
+    BackupNotFound(String),
+
+    /// The backup is not a consistent datafile that can be restored.
+    InvalidBackup { id: String, source: Box<Error> },
+}
+
+impl Error {
+    /// Returns a function wrapping an I/O error with the description of what was being done.
+    pub(super) fn io(context: impl Into<String>) -> impl FnOnce(io::Error) -> Error {
+        let context = context.into();
+        move |source| Error::Io { context, source }
+    }
+
+    /// Returns an error if foreign key constraints are violated.
+    pub(super) fn check_violations(context: &str, violations: usize) -> Result<()> {
+        if violations > 0 {
+            return Err(Error::ForeignKeyViolation {
+                context: String::from(context),
+                violations,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Io { context, .. } => write!(f, "{}", context),
+            Error::Sqlite(error) => write!(f, "{}", error),
+            Error::Csv(error) => write!(f, "{}", error),
+            Error::EmptyDiary => write!(f, "The diary is empty"),
+            Error::CorruptData(description) => {
+                write!(f, "Invalid data in datafile: {}", description)
+            }
+            Error::ForeignKeyViolation {
+                context,
+                violations,
+            } => write!(
+                f,
+                "{}: {} foreign key constraints are violated",
+                context, violations
+            ),
+            Error::UnsupportedVersion { version, latest } => write!(
+                f,
+                "The datafile was written by a newer version of genee (datafile version {}, supported up to {})",
+                version, latest
+            ),
+            Error::MigrationRequired { version, latest } => write!(
+                f,
+                "The datafile needs to be updated from version {} to {}, which is not possible in read-only mode",
+                version, latest
+            ),
+            Error::Locked { holder } => write!(
+                f,
+                "The datafile is in use by another running instance of genee{}, use --read-only to open it without editing",
+                match holder {
+                    Some(holder) => format!(" (process {})", holder),
+                    None => String::new(),
+                }
+            ),
+            Error::PassphraseRequired => write!(
+                f,
+                "The datafile is encrypted, a passphrase is required to open it"
+            ),
+            Error::ReadOnly => write!(
+                f,
+                "The datafile is opened in read-only mode, it cannot be modified"
+            ),
+            Error::WrongPassphrase => {
+                write!(f, "Wrong passphrase, or the file is not a datafile")
+            }
+            Error::WrongBackupPassphrase(id) => write!(
+                f,
+                "Wrong passphrase for backup {}, it may have been encrypted with a previous passphrase of the datafile",
+                id
+            ),
+            Error::EncryptionUnsupported => write!(
+                f,
+                "genee was built without support for encrypted datafiles (the \"encryption\" feature)"
+            ),
+            Error::NonExistingCategory(id) => write!(f, "Category {} does not exist", id),
+            Error::InvalidInput(description) => write!(f, "Invalid input: {}", description),
+            Error::BackupNotFound(id) => write!(f, "Could not find backup {}", id),
+            Error::InvalidBackup { id, .. } => write!(f, "Backup {} is corrupted", id),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            Error::Sqlite(error) => error.source(),
+            Error::Csv(error) => error.source(),
+            Error::InvalidBackup { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<rusqlite::Error> for Error {
+    fn from(error: rusqlite::Error) -> Self {
+        Error::Sqlite(error)
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(error: csv::Error) -> Self {
+        Error::Csv(error)
+    }
+}
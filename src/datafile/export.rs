@@ -0,0 +1,86 @@
+//! Export of habit data to delimited text files, e.g. CSV or TSV.
+use super::{DayEntry, DiaryStore, EntryStatus, Error, Result};
+use chrono::NaiveDate;
+use std::io::Write;
+
+/// How the days that were not tracked are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UntrackedDays {
+    /// The row of the day has empty cells.
+    #[default]
+    Empty,
+
+    /// The row of the day has zeros, like a tracked day without entries.
+    Zero,
+
+    /// The day has no row.
+    Omit,
+}
+
+/// Options of the export to delimited text files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DelimitedExportOptions {
+    /// Separator of the columns, e.g. `b','` for CSV or `b'\t'` for TSV.
+    pub delimiter: u8,
+
+    /// Whether the hidden categories get a column.
+    pub include_hidden: bool,
+
+    /// How the days that were not tracked are written.
+    pub untracked: UntrackedDays,
+}
+
+/// Writes the habit data between two dates (inclusive) with one row per date and one column per category.
+/// Done entries are written as 1 or as their value, partially done and skipped entries as
+/// "partial" and "skipped", and missing entries as 0.
+/// Returns the number of rows written, not including the header.
+pub fn export_delimited<S: DiaryStore + ?Sized>(
+    store: &S,
+    writer: impl Write,
+    from: &NaiveDate,
+    until: &NaiveDate,
+    options: &DelimitedExportOptions,
+) -> Result<usize> {
+    let categories: Vec<_> = store
+        .get_categories()?
+        .into_iter()
+        .filter(|category| options.include_hidden || !category.hidden)
+        .collect();
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(options.delimiter)
+        .from_writer(writer);
+    writer.write_record(
+        std::iter::once("date").chain(categories.iter().map(|category| category.name.as_str())),
+    )?;
+    let mut row_count = 0;
+    for record in store.iter_days(*from..=*until) {
+        let record = record?;
+        let cells: Vec<String> = match (&record.entries, options.untracked) {
+            (Some(_), _) => categories
+                .iter()
+                .map(|category| format_entry(record.get_entry(category.id)))
+                .collect(),
+            (None, UntrackedDays::Empty) => vec![String::new(); categories.len()],
+            (None, UntrackedDays::Zero) => vec![format_entry(None); categories.len()],
+            (None, UntrackedDays::Omit) => continue,
+        };
+        writer.write_record(std::iter::once(record.date.to_string()).chain(cells))?;
+        row_count += 1;
+    }
+    writer
+        .flush()
+        .map_err(Error::io("Could not write the export"))?;
+    Ok(row_count)
+}
+
+fn format_entry(entry: Option<&DayEntry>) -> String {
+    match entry {
+        None => String::from("0"),
+        Some(entry) => match (entry.status, entry.value) {
+            (EntryStatus::Done, Some(value)) => value.to_string(),
+            (EntryStatus::Done, None) => String::from("1"),
+            (EntryStatus::Partial, _) => String::from("partial"),
+            (EntryStatus::Skipped, _) => String::from("skipped"),
+        },
+    }
+}
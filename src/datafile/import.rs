@@ -0,0 +1,344 @@
+//! Import of habit data from other sources, e.g. spreadsheets exported to CSV.
+use super::{
+    Category, CategoryDetails, CategoryId, DayEntry, DayRecord, DiaryDataSqlite, DiaryStore,
+    EntryStatus, Error, Polarity, Result, Schedule,
+};
+use chrono::NaiveDate;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Read,
+};
+
+/// Default cell values marking an entry as done.
+pub const DEFAULT_TRUTHY_VALUES: &[&str] = &["x", "1", "✓", "yes", "true"];
+
+/// How the imported days that are already tracked in the diary are handled.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ConflictPolicy {
+    /// The tracked days are left unchanged.
+    #[default]
+    Skip,
+
+    /// The entries of the imported categories are replaced, the other entries are kept.
+    Overwrite,
+
+    /// The imported entries are added to the existing ones.
+    Union,
+}
+
+/// Options of the import from delimited text files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DelimitedImportOptions {
+    /// Separator of the columns, e.g. `b','` for CSV or `b'\t'` for TSV.
+    pub delimiter: u8,
+
+    /// Header of the column holding the dates. Defaults to the first column.
+    pub date_column: Option<String>,
+
+    /// Format of the dates, as accepted by `NaiveDate::parse_from_str`.
+    pub date_format: String,
+
+    /// Categories of the columns. The columns that are not listed are mapped to the category
+    /// with the same name if there is one, otherwise they are ignored.
+    pub mapping: Vec<(String, CategoryId)>,
+
+    /// Cell values marking an entry as done, compared without regard to case.
+    pub truthy_values: Vec<String>,
+
+    /// Numeric cells at least this large mark an entry as done.
+    /// Without a threshold, any positive number marks an entry as done.
+    pub threshold: Option<f64>,
+
+    /// How the days that are already tracked are handled.
+    pub conflict_policy: ConflictPolicy,
+}
+
+impl Default for DelimitedImportOptions {
+    fn default() -> Self {
+        DelimitedImportOptions {
+            delimiter: b',',
+            date_column: None,
+            date_format: String::from("%Y-%m-%d"),
+            mapping: vec![],
+            truthy_values: DEFAULT_TRUTHY_VALUES
+                .iter()
+                .map(|value| value.to_string())
+                .collect(),
+            threshold: None,
+            conflict_policy: ConflictPolicy::default(),
+        }
+    }
+}
+
+/// Summary of an import.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImportReport {
+    /// Imported columns, or habits of other apps, and the categories they are mapped to.
+    pub mapped_columns: Vec<(String, CategoryId)>,
+
+    /// Columns that are not mapped to any category.
+    pub ignored_columns: Vec<String>,
+
+    /// Categories created for the import. In a dry run, they are not created
+    /// and their identifiers are only placeholders.
+    pub created_categories: Vec<Category>,
+
+    /// Number of days that were not tracked before.
+    pub new_days: usize,
+
+    /// Number of tracked days left unchanged because of the conflict policy.
+    pub skipped_days: usize,
+
+    /// Number of tracked days whose entries are replaced or extended.
+    pub updated_days: usize,
+
+    /// Number of imported entries, not counting the skipped days.
+    pub entries: usize,
+
+    /// Non-empty cell values that were neither truthy nor numeric, so they were not imported as done.
+    pub unrecognized_values: BTreeSet<String>,
+}
+
+/// Imports the habit data of a delimited text file with one row per date and one column per category.
+/// The numeric cells are imported as the values of the entries. The rows without any done cell
+/// are only imported with the `Overwrite` policy, which clears the entries of the imported categories.
+/// Nothing is written in a dry run, but the returned report describes what would be imported.
+pub fn import_delimited<S: DiaryStore + ?Sized>(
+    store: &mut S,
+    reader: impl Read,
+    options: &DelimitedImportOptions,
+    dry_run: bool,
+) -> Result<ImportReport> {
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(options.delimiter)
+        .flexible(true)
+        .from_reader(reader);
+    let headers = reader.headers()?.clone();
+    let date_index = match options.date_column {
+        Some(ref date_column) => headers
+            .iter()
+            .position(|header| header == date_column)
+            .ok_or_else(|| {
+                Error::InvalidInput(format!("there is no \"{}\" column", date_column))
+            })?,
+        None => 0,
+    };
+    let categories = store.get_categories()?;
+    let mut report = ImportReport::default();
+    let mut columns = vec![];
+    for (index, header) in headers.iter().enumerate() {
+        if index == date_index {
+            continue;
+        }
+        let category_id = options
+            .mapping
+            .iter()
+            .find(|(column, _)| column == header)
+            .map(|(_, category_id)| *category_id)
+            .or_else(|| {
+                categories
+                    .iter()
+                    .find(|category| category.name == header)
+                    .map(|category| category.id)
+            });
+        match category_id {
+            Some(category_id) => {
+                report
+                    .mapped_columns
+                    .push((header.to_string(), category_id));
+                columns.push((index, category_id));
+            }
+            None => report.ignored_columns.push(header.to_string()),
+        }
+    }
+
+    let mut imported: BTreeMap<NaiveDate, BTreeMap<CategoryId, DayEntry>> = BTreeMap::new();
+    for row in reader.records() {
+        let row = row?;
+        let date_cell = row.get(date_index).unwrap_or_default().trim();
+        let date = NaiveDate::parse_from_str(date_cell, &options.date_format).map_err(|_| {
+            Error::InvalidInput(format!(
+                "invalid date \"{}\" on line {}",
+                date_cell,
+                row.position().map_or(0, |position| position.line())
+            ))
+        })?;
+        let mut day = BTreeMap::new();
+        for (index, category_id) in &columns {
+            let cell = row.get(*index).unwrap_or_default().trim();
+            match parse_cell(cell, options) {
+                Some((true, value)) => {
+                    let entry = DayEntry {
+                        category_id: *category_id,
+                        status: EntryStatus::Done,
+                        value,
+                    };
+                    day.insert(*category_id, entry);
+                }
+                Some((false, _)) => (),
+                None => {
+                    report.unrecognized_values.insert(cell.to_string());
+                }
+            }
+        }
+        // The rows without any done cell do not mark their day as tracked unless they overwrite it
+        if !day.is_empty() || options.conflict_policy == ConflictPolicy::Overwrite {
+            imported.entry(date).or_default().extend(day);
+        }
+    }
+
+    let imported_categories: BTreeSet<CategoryId> = columns
+        .iter()
+        .map(|(_, category_id)| *category_id)
+        .collect();
+    apply_import(
+        store,
+        imported,
+        &imported_categories,
+        options.conflict_policy,
+        dry_run,
+        &mut report,
+    )?;
+    Ok(report)
+}
+
+/// Returns the identifier of the category with the name, creating the category if it does not exist.
+/// In a dry run, the category is not created and gets a placeholder identifier.
+/// The created categories are added to `categories` and to the report.
+pub(super) fn get_or_create_category(
+    datafile: &DiaryDataSqlite,
+    categories: &mut Vec<Category>,
+    name: &str,
+    hidden: bool,
+    dry_run: bool,
+    report: &mut ImportReport,
+) -> Result<CategoryId> {
+    if let Some(category) = categories.iter().find(|category| category.name == name) {
+        return Ok(category.id);
+    }
+    let category = if dry_run {
+        let id = categories
+            .iter()
+            .map(|category| category.id.0 + 1)
+            .max()
+            .unwrap_or(1);
+        Category {
+            id: CategoryId(id),
+            name: name.to_string(),
+            hidden,
+            polarity: Polarity::default(),
+            schedule: Schedule::default(),
+            details: CategoryDetails::default(),
+        }
+    } else {
+        datafile.add_category(name)?;
+        if hidden {
+            datafile.hide_category(name)?;
+        }
+        datafile
+            .get_categories()?
+            .into_iter()
+            .find(|category| category.name == name)
+            .ok_or_else(|| Error::CorruptData(format!("category \"{}\" was not created", name)))?
+    };
+    let id = category.id;
+    report.created_categories.push(category.clone());
+    categories.push(category);
+    Ok(id)
+}
+
+/// Writes the imported entries of each date according to the conflict policy in a single batch,
+/// and counts the days and the entries in the report.
+pub(super) fn apply_import<S: DiaryStore + ?Sized>(
+    store: &mut S,
+    imported: BTreeMap<NaiveDate, BTreeMap<CategoryId, DayEntry>>,
+    imported_categories: &BTreeSet<CategoryId>,
+    conflict_policy: ConflictPolicy,
+    dry_run: bool,
+    report: &mut ImportReport,
+) -> Result<()> {
+    let (Some(first), Some(last)) = (imported.keys().next(), imported.keys().next_back()) else {
+        return Ok(());
+    };
+    let existing: BTreeMap<NaiveDate, DayRecord> = store
+        .get_days(first, last)?
+        .into_iter()
+        .map(|record| (record.date, record))
+        .collect();
+    let mut new_items = vec![];
+    for (date, day) in imported {
+        let kept: Vec<DayEntry> = match existing
+            .get(&date)
+            .and_then(|record| record.entries.as_ref())
+        {
+            None => {
+                report.new_days += 1;
+                vec![]
+            }
+            Some(_) if conflict_policy == ConflictPolicy::Skip => {
+                report.skipped_days += 1;
+                continue;
+            }
+            Some(entries) => {
+                report.updated_days += 1;
+                entries
+                    .iter()
+                    .filter(|entry| {
+                        conflict_policy == ConflictPolicy::Union
+                            || !imported_categories.contains(&entry.category_id)
+                    })
+                    .copied()
+                    .collect()
+            }
+        };
+        report.entries += day.len();
+        let mut entries: BTreeMap<CategoryId, DayEntry> = kept
+            .into_iter()
+            .map(|entry| (entry.category_id, entry))
+            .collect();
+        for (category_id, entry) in day {
+            // The imported entries only add a status or a value to the existing ones
+            let entry = match entries.get(&category_id) {
+                Some(existing) => DayEntry {
+                    status: if entry.status == EntryStatus::Done {
+                        existing.status
+                    } else {
+                        entry.status
+                    },
+                    value: entry.value.or(existing.value),
+                    ..entry
+                },
+                None => entry,
+            };
+            entries.insert(category_id, entry);
+        }
+        new_items.push((date, entries.into_values().collect()));
+    }
+    if !dry_run {
+        store.update_entries_batch(&new_items)?;
+    }
+    Ok(())
+}
+
+/// Returns whether a cell marks an entry as done and the value of the numeric cells,
+/// or `None` if the cell is not recognized.
+fn parse_cell(cell: &str, options: &DelimitedImportOptions) -> Option<(bool, Option<f64>)> {
+    if cell.is_empty() {
+        return Some((false, None));
+    }
+    if options
+        .truthy_values
+        .iter()
+        .any(|value| value.to_lowercase() == cell.to_lowercase())
+    {
+        return Some((true, None));
+    }
+    let number = cell
+        .parse::<f64>()
+        .ok()
+        .filter(|number| number.is_finite())?;
+    let done = options
+        .threshold
+        .map_or(number > 0.0, |threshold| number >= threshold);
+    Some((done, Some(number)))
+}
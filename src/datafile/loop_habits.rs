@@ -0,0 +1,158 @@
+//! Import of the backups of Loop Habit Tracker, which are SQLite databases.
+use super::{
+    CategoryId, ConflictPolicy, DayEntry, DiaryDataSqlite, DiaryStore, EntryStatus, Error,
+    ImportReport, Result,
+    import::{apply_import, get_or_create_category},
+};
+use chrono::{DateTime, NaiveDate};
+use rusqlite::{Connection, OpenFlags};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+/// Value of the repetitions of boolean habits that were checked by the user.
+const YES_MANUAL: i64 = 2;
+
+/// Value of the repetitions of boolean habits that were skipped.
+const SKIP: i64 = 3;
+
+/// Type of the habits with numeric values, which are stored multiplied by 1000.
+const NUMERICAL_TYPE: i64 = 1;
+
+/// A habit of a Loop Habit Tracker backup.
+struct Habit {
+    id: i64,
+    name: String,
+    archived: bool,
+    numerical: bool,
+}
+
+/// Imports the habits and the repetitions of a Loop Habit Tracker backup.
+/// The habits are mapped to the categories with the same name, which are created if they do
+/// not exist yet. The categories created for archived habits are hidden.
+/// Nothing is written in a dry run, but the returned report describes what would be imported.
+pub fn import_loop_backup(
+    datafile: &mut DiaryDataSqlite,
+    path: &Path,
+    conflict_policy: ConflictPolicy,
+    dry_run: bool,
+) -> Result<ImportReport> {
+    let backup = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let habits = read_habits(&backup, path)?;
+
+    let mut report = ImportReport::default();
+    let mut categories = datafile.get_categories()?;
+    let mut category_ids = BTreeMap::new();
+    for habit in &habits {
+        let category_id = get_or_create_category(
+            datafile,
+            &mut categories,
+            &habit.name,
+            habit.archived,
+            dry_run,
+            &mut report,
+        )?;
+        report
+            .mapped_columns
+            .push((habit.name.clone(), category_id));
+        category_ids.insert(habit.id, (category_id, habit.numerical));
+    }
+
+    let mut imported: BTreeMap<NaiveDate, BTreeMap<CategoryId, DayEntry>> = BTreeMap::new();
+    // The first versions of the app only stored the checked days
+    let value_column = if has_column(&backup, "Repetitions", "value")? {
+        "value"
+    } else {
+        "2"
+    };
+    let mut statement = backup.prepare(&format!(
+        "SELECT habit, timestamp, {} FROM Repetitions ORDER BY timestamp",
+        value_column
+    ))?;
+    let mut rows = statement.query([])?;
+    while let Some(row) = rows.next()? {
+        let habit_id: i64 = row.get(0)?;
+        let timestamp: i64 = row.get(1)?;
+        let value: i64 = row.get(2)?;
+        let Some(&(category_id, numerical)) = category_ids.get(&habit_id) else {
+            continue;
+        };
+        let (status, value) = match value {
+            value if numerical && value > 0 => (EntryStatus::Done, Some(value as f64 / 1000.0)),
+            YES_MANUAL if !numerical => (EntryStatus::Done, None),
+            SKIP => (EntryStatus::Skipped, None),
+            _ => continue,
+        };
+        // The repetitions are stored at midnight UTC of their day
+        let date = DateTime::from_timestamp_millis(timestamp)
+            .ok_or_else(|| Error::InvalidInput(format!("invalid timestamp {}", timestamp)))?
+            .date_naive();
+        let entry = DayEntry {
+            category_id,
+            status,
+            value,
+        };
+        imported.entry(date).or_default().insert(category_id, entry);
+    }
+
+    let imported_categories: BTreeSet<CategoryId> = category_ids
+        .values()
+        .map(|(category_id, _)| *category_id)
+        .collect();
+    apply_import(
+        datafile,
+        imported,
+        &imported_categories,
+        conflict_policy,
+        dry_run,
+        &mut report,
+    )?;
+    Ok(report)
+}
+
+/// Returns the habits of the backup in the order they are displayed in the app.
+fn read_habits(backup: &Connection, path: &Path) -> Result<Vec<Habit>> {
+    let tables: usize = backup.query_row(
+        "SELECT COUNT(*) FROM sqlite_master
+            WHERE type='table' AND name IN ('Habits', 'Repetitions')",
+        [],
+        |row| row.get(0),
+    )?;
+    if tables != 2 {
+        return Err(Error::InvalidInput(format!(
+            "{} is not a Loop Habit Tracker backup",
+            path.display()
+        )));
+    }
+    // The numerical habits were introduced in later versions of the app
+    let type_column = if has_column(backup, "Habits", "type")? {
+        "type"
+    } else {
+        "0"
+    };
+    let mut statement = backup.prepare(&format!(
+        "SELECT id, name, archived, {} FROM Habits ORDER BY position, id",
+        type_column
+    ))?;
+    let habits = statement
+        .query_map([], |row| {
+            Ok(Habit {
+                id: row.get(0)?,
+                name: row.get::<_, String>(1)?.trim().to_string(),
+                archived: row.get::<_, Option<bool>>(2)?.unwrap_or_default(),
+                numerical: row.get::<_, i64>(3)? == NUMERICAL_TYPE,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+    Ok(habits)
+}
+
+fn has_column(connection: &Connection, table: &str, column: &str) -> Result<bool> {
+    let count: usize = connection.query_row(
+        "SELECT COUNT(*) FROM pragma_table_info(?1) WHERE name=?2",
+        [table, column],
+        |row| row.get(0),
+    )?;
+    Ok(count > 0)
+}
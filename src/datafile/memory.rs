@@ -0,0 +1,271 @@
+//! In-memory storage of the habit data, which is not persisted anywhere.
+use super::{
+    Category, CategoryId, DayEntry, DayRecord, DiaryStore, EntryStatus, Error, ExcusedRange,
+    Result, SuccessfulUpdate, Target, store,
+};
+use chrono::NaiveDate;
+use std::collections::{BTreeMap, HashMap};
+
+/// Habit data kept in memory, e.g. for testing code working with any `DiaryStore`.
+#[derive(Debug, Clone, Default)]
+pub struct DiaryDataMemory {
+    categories: Vec<Category>,
+    targets: HashMap<CategoryId, Target>,
+
+    /// Entries of the tracked days as (status, value) pairs, keyed by category ID.
+    days: BTreeMap<NaiveDate, BTreeMap<CategoryId, (EntryStatus, Option<f64>)>>,
+    notes: HashMap<NaiveDate, String>,
+    excused_ranges: Vec<ExcusedRange>,
+}
+
+impl DiaryDataMemory {
+    /// Creates an empty diary with visible categories of the specified names,
+    /// numbered from 1 in the specified order.
+    pub fn new(headers: &[String]) -> Self {
+        Self::from_categories(
+            headers
+                .iter()
+                .enumerate()
+                .map(|(idx, name)| Category {
+                    id: CategoryId(idx + 1),
+                    name: name.clone(),
+                    hidden: false,
+                    polarity: Default::default(),
+                    schedule: Default::default(),
+                    details: Default::default(),
+                })
+                .collect(),
+        )
+    }
+
+    /// Creates an empty diary with the specified categories, listed in display order.
+    pub fn from_categories(categories: Vec<Category>) -> Self {
+        DiaryDataMemory {
+            categories,
+            ..Default::default()
+        }
+    }
+
+    /// Sets or clears (when `target` is `None`) the frequency target of a category.
+    pub fn set_target(&mut self, category_id: CategoryId, target: Option<Target>) -> Result<()> {
+        self.check_category(category_id)?;
+        match target {
+            Some(target) => self.targets.insert(category_id, target),
+            None => self.targets.remove(&category_id),
+        };
+        Ok(())
+    }
+
+    fn check_category(&self, category_id: CategoryId) -> Result<()> {
+        if !self
+            .categories
+            .iter()
+            .any(|category| category.id == category_id)
+        {
+            return Err(Error::NonExistingCategory(category_id));
+        }
+        Ok(())
+    }
+
+    fn get_entries(
+        &self,
+        from: &NaiveDate,
+        until: &NaiveDate,
+    ) -> impl Iterator<Item = (NaiveDate, CategoryId, EntryStatus, Option<f64>)> {
+        self.days.range(*from..=*until).flat_map(|(date, entries)| {
+            entries
+                .iter()
+                .map(|(id, (status, value))| (*date, *id, *status, *value))
+        })
+    }
+}
+
+impl DiaryStore for DiaryDataMemory {
+    fn is_read_only(&self) -> bool {
+        false
+    }
+
+    fn get_categories(&self) -> Result<Vec<Category>> {
+        Ok(self.categories.clone())
+    }
+
+    fn get_targets(&self) -> Result<HashMap<CategoryId, Target>> {
+        Ok(self.targets.clone())
+    }
+
+    fn get_days(&self, from: &NaiveDate, until: &NaiveDate) -> Result<Vec<DayRecord>> {
+        let mut records = vec![];
+        for date in from.iter_days().take_while(|date| date <= until) {
+            let mut record = DayRecord::untracked(date);
+            record.entries = self.days.get(&date).map(|entries| {
+                entries
+                    .iter()
+                    .map(|(id, (status, value))| DayEntry {
+                        category_id: *id,
+                        status: *status,
+                        value: *value,
+                    })
+                    .collect()
+            });
+            record.note = self.notes.get(&date).cloned();
+            for range in self
+                .excused_ranges
+                .iter()
+                .filter(|range| range.contains(&date))
+            {
+                match range.category_id {
+                    None => record.excused = true,
+                    Some(id) => record.excused_categories.push(id),
+                }
+            }
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    fn get_values(
+        &self,
+        from: &NaiveDate,
+        until: &NaiveDate,
+    ) -> Result<Vec<(NaiveDate, CategoryId, f64)>> {
+        Ok(self
+            .get_entries(from, until)
+            .filter_map(|(date, id, _, value)| Some((date, id, value?)))
+            .collect())
+    }
+
+    fn get_statuses(
+        &self,
+        from: &NaiveDate,
+        until: &NaiveDate,
+    ) -> Result<Vec<(NaiveDate, CategoryId, EntryStatus)>> {
+        Ok(self
+            .get_entries(from, until)
+            .map(|(date, id, status, _)| (date, id, status))
+            .collect())
+    }
+
+    fn get_excused_ranges(&self) -> Result<Vec<ExcusedRange>> {
+        Ok(self.excused_ranges.clone())
+    }
+
+    fn get_note(&self, date: &NaiveDate) -> Result<Option<String>> {
+        Ok(self.notes.get(date).cloned())
+    }
+
+    fn is_empty(&self) -> Result<bool> {
+        Ok(self.days.is_empty())
+    }
+
+    fn get_date_range(&self) -> Result<(NaiveDate, NaiveDate)> {
+        match (self.days.keys().next(), self.days.keys().next_back()) {
+            (Some(first), Some(last)) => Ok((*first, *last)),
+            _ => Err(Error::EmptyDiary),
+        }
+    }
+
+    fn update_data(
+        &mut self,
+        date: &NaiveDate,
+        new_row: &[CategoryId],
+    ) -> Result<SuccessfulUpdate> {
+        for id in new_row {
+            self.check_category(*id)?;
+        }
+        let previous_entries = self.days.remove(date);
+        let entries = new_row
+            .iter()
+            .map(|id| {
+                let entry = previous_entries
+                    .as_ref()
+                    .and_then(|entries| entries.get(id))
+                    .copied()
+                    .unwrap_or((EntryStatus::Done, None));
+                (*id, entry)
+            })
+            .collect();
+        self.days.insert(*date, entries);
+        if previous_entries.is_none() {
+            Ok(SuccessfulUpdate::AddedNew)
+        } else {
+            Ok(SuccessfulUpdate::ReplacedExisting)
+        }
+    }
+
+    fn set_value(
+        &mut self,
+        date: &NaiveDate,
+        category_id: CategoryId,
+        value: Option<f64>,
+    ) -> Result<()> {
+        self.check_category(category_id)?;
+        self.days
+            .entry(*date)
+            .or_default()
+            .entry(category_id)
+            .or_insert((EntryStatus::Done, None))
+            .1 = value;
+        Ok(())
+    }
+
+    fn set_status(
+        &mut self,
+        date: &NaiveDate,
+        category_id: CategoryId,
+        status: Option<EntryStatus>,
+    ) -> Result<()> {
+        self.check_category(category_id)?;
+        let entries = self.days.entry(*date).or_default();
+        match status {
+            Some(status) => entries.entry(category_id).or_insert((status, None)).0 = status,
+            None => {
+                entries.remove(&category_id);
+            }
+        }
+        Ok(())
+    }
+
+    fn set_note(&mut self, date: &NaiveDate, note: &str) -> Result<()> {
+        if note.is_empty() {
+            self.notes.remove(date);
+        } else {
+            self.notes.insert(*date, String::from(note));
+        }
+        Ok(())
+    }
+
+    fn set_excused(
+        &mut self,
+        category_id: Option<CategoryId>,
+        from: &NaiveDate,
+        until: &NaiveDate,
+        excused: bool,
+    ) -> Result<()> {
+        if let Some(id) = category_id {
+            self.check_category(id)?;
+        }
+        // Overlapping and adjacent ranges are affected
+        let (affected_ranges, mut ranges): (Vec<ExcusedRange>, Vec<ExcusedRange>) =
+            self.excused_ranges.iter().partition(|range| {
+                range.category_id == category_id
+                    && (range.from - *until).num_days() <= 1
+                    && (*from - range.until).num_days() <= 1
+            });
+        let affected_ranges: Vec<(NaiveDate, NaiveDate)> = affected_ranges
+            .iter()
+            .map(|range| (range.from, range.until))
+            .collect();
+        for (range_from, range_until) in
+            store::split_excused_ranges(&affected_ranges, from, until, excused)
+        {
+            ranges.push(ExcusedRange {
+                category_id,
+                from: range_from,
+                until: range_until,
+            });
+        }
+        ranges.sort_by_key(|range| range.from);
+        self.excused_ranges = ranges;
+        Ok(())
+    }
+}
@@ -0,0 +1,236 @@
+//! Versioned schema migrations of SQLite datafiles.
+use super::{Error, Result};
+use rusqlite::{Connection, OptionalExtension, params};
+
+/// A single step of upgrading the schema of a datafile.
+#[derive(Debug, PartialEq)]
+pub struct Migration {
+    /// Schema version of the datafile after the migration.
+    pub version: usize,
+
+    /// Human readable summary of the migration.
+    pub description: &'static str,
+
+    /// SQL statements performing the migration.
+    sql: &'static str,
+}
+
+/// All migrations in the order they are applied. The schema version of a datafile
+/// is the version of the last migration applied to it.
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "Add the Info table and hidden categories",
+        sql: "DROP TABLE IF EXISTS Info;
+            CREATE TABLE Info(
+                info_id INTEGER PRIMARY KEY AUTOINCREMENT,
+                info_name TEXT UNIQUE NOT NULL,
+                info_value TEXT NOT NULL
+            );
+            ALTER TABLE Category ADD COLUMN hidden INTEGER NOT NULL DEFAULT 0;",
+    },
+    Migration {
+        version: 2,
+        description: "Add numeric values to the entries",
+        sql: "ALTER TABLE EntryToCategories ADD COLUMN value REAL;",
+    },
+    Migration {
+        version: 3,
+        description: "Add notes to the days",
+        sql: "CREATE TABLE DateNote(
+                date INTEGER PRIMARY KEY,
+                note TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );",
+    },
+    Migration {
+        version: 4,
+        description: "Add frequency targets to the categories",
+        sql: "ALTER TABLE Category ADD COLUMN target_kind TEXT;
+            ALTER TABLE Category ADD COLUMN target_count INTEGER;
+            ALTER TABLE Category ADD COLUMN target_days INTEGER;",
+    },
+    Migration {
+        version: 5,
+        description: "Add the display order of the categories",
+        sql: "ALTER TABLE Category ADD COLUMN sort_order INTEGER NOT NULL DEFAULT 0;
+            UPDATE Category SET sort_order=category_id;",
+    },
+    Migration {
+        version: 6,
+        description: "Add display names, descriptions, symbols and colours to the categories",
+        sql: "ALTER TABLE Category ADD COLUMN display_name TEXT;
+            ALTER TABLE Category ADD COLUMN description TEXT;
+            ALTER TABLE Category ADD COLUMN symbol TEXT;
+            ALTER TABLE Category ADD COLUMN color TEXT;",
+    },
+    Migration {
+        version: 7,
+        description: "Add habits to avoid",
+        sql: "ALTER TABLE Category ADD COLUMN avoid INTEGER NOT NULL DEFAULT 0;",
+    },
+    Migration {
+        version: 8,
+        description: "Add schedules to the categories",
+        sql: "ALTER TABLE Category ADD COLUMN schedule_weekdays INTEGER NOT NULL DEFAULT 127;
+            ALTER TABLE Category ADD COLUMN schedule_every INTEGER;
+            ALTER TABLE Category ADD COLUMN schedule_anchor INTEGER;",
+    },
+    Migration {
+        version: 9,
+        description: "Add excused date ranges",
+        sql: "CREATE TABLE ExcusedRange(
+                category_id INTEGER REFERENCES Category(category_id) ON DELETE CASCADE,
+                from_date INTEGER NOT NULL,
+                until_date INTEGER NOT NULL
+            );",
+    },
+    Migration {
+        version: 10,
+        description: "Add statuses to the entries",
+        sql: "ALTER TABLE EntryToCategories ADD COLUMN status INTEGER NOT NULL DEFAULT 0;",
+    },
+    Migration {
+        version: 11,
+        description: "Store the dates as day numbers instead of UTC timestamps",
+        // Timestamps are rounded down to the day, also for the dates before 1970.
+        // The rows falling on the same day are merged: the notes are joined, the largest value
+        // and the status counting the most (done before partially done before skipped) are kept.
+        sql: "CREATE TABLE DateEntryDays(
+                date INTEGER PRIMARY KEY,
+                created_at INTEGER NOT NULL
+            );
+            INSERT INTO DateEntryDays (date, created_at)
+                SELECT (date - (date % 86400 + 86400) % 86400) / 86400 AS day, MIN(created_at)
+                FROM DateEntry GROUP BY day;
+            DROP TABLE DateEntry;
+            ALTER TABLE DateEntryDays RENAME TO DateEntry;
+            CREATE TABLE EntryToCategoriesDays(
+                date INTEGER NOT NULL REFERENCES DateEntry(date) ON DELETE CASCADE,
+                category_id INTEGER NOT NULL REFERENCES Category(category_id) ON DELETE CASCADE,
+                value REAL,
+                status INTEGER NOT NULL DEFAULT 0,
+                PRIMARY KEY(category_id, date)
+            );
+            INSERT INTO EntryToCategoriesDays (date, category_id, value, status)
+                SELECT (date - (date % 86400 + 86400) % 86400) / 86400 AS day, category_id,
+                    MAX(value), MIN(status)
+                FROM EntryToCategories GROUP BY day, category_id;
+            DROP TABLE EntryToCategories;
+            ALTER TABLE EntryToCategoriesDays RENAME TO EntryToCategories;
+            CREATE TABLE DateNoteDays(
+                date INTEGER PRIMARY KEY,
+                note TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            );
+            INSERT INTO DateNoteDays (date, note, created_at)
+                SELECT (date - (date % 86400 + 86400) % 86400) / 86400 AS day,
+                    group_concat(note, char(10) ORDER BY date), MIN(created_at)
+                FROM DateNote GROUP BY day;
+            DROP TABLE DateNote;
+            ALTER TABLE DateNoteDays RENAME TO DateNote;
+            UPDATE ExcusedRange SET
+                from_date=(from_date - (from_date % 86400 + 86400) % 86400) / 86400,
+                until_date=(until_date - (until_date % 86400 + 86400) % 86400) / 86400;
+            UPDATE Category SET
+                schedule_anchor=(schedule_anchor - (schedule_anchor % 86400 + 86400) % 86400) / 86400
+                WHERE schedule_anchor NOTNULL;",
+    },
+    Migration {
+        version: 12,
+        description: "Index the entries by date",
+        sql: "CREATE INDEX EntryToCategoriesDate ON EntryToCategories(date);",
+    },
+];
+
+/// Returns the schema version of the datafiles written by this version of the program.
+pub fn latest_version() -> usize {
+    MIGRATIONS.last().map_or(0, |migration| migration.version)
+}
+
+/// Returns the schema version stored in the datafile, or 0 if it predates versioning.
+pub fn get_version(connection: &Connection) -> Result<usize> {
+    let Ok(mut statement) =
+        connection.prepare("SELECT info_value FROM Info WHERE info_name=\"version\"")
+    else {
+        return Ok(0);
+    };
+    let Some(version) = statement
+        .query_row([], |row| row.get::<usize, String>(0))
+        .optional()?
+    else {
+        return Ok(0);
+    };
+    version
+        .parse()
+        .map_err(|_| Error::CorruptData(format!("invalid schema version {}", version)))
+}
+
+pub fn set_version(connection: &Connection, version: usize) -> Result<()> {
+    connection.execute(
+        "INSERT INTO Info (info_name, info_value) VALUES (\"version\", ?1)
+            ON CONFLICT(info_name) DO UPDATE SET info_value=excluded.info_value",
+        params![version],
+    )?;
+    Ok(())
+}
+
+/// Returns the migrations not yet applied to a datafile of the specified version.
+/// Fails if the datafile was written by a newer version of the program.
+pub fn get_pending(version: usize) -> Result<&'static [Migration]> {
+    if version > latest_version() {
+        return Err(Error::UnsupportedVersion {
+            version,
+            latest: latest_version(),
+        });
+    }
+    Ok(&MIGRATIONS[MIGRATIONS.partition_point(|migration| migration.version <= version)..])
+}
+
+/// Applies the pending migrations, each of them in a separate transaction.
+/// Foreign key enforcement is suspended meanwhile so that tables can be rebuilt,
+/// but no migration may add foreign key violations to the ones already present.
+pub fn run(connection: &mut Connection) -> Result<()> {
+    run_until(connection, latest_version())
+}
+
+/// Applies the pending migrations up to the specified schema version.
+pub(super) fn run_until(connection: &mut Connection, version: usize) -> Result<()> {
+    let pending = get_pending(get_version(connection)?)?;
+    let pending = &pending[..pending
+        .iter()
+        .take_while(|migration| migration.version <= version)
+        .count()];
+    if pending.is_empty() {
+        return Ok(());
+    }
+    connection.execute_batch("PRAGMA foreign_keys=OFF;")?;
+    let result = run_migrations(connection, pending);
+    connection.execute_batch("PRAGMA foreign_keys=ON;")?;
+    result
+}
+
+fn run_migrations(connection: &mut Connection, migrations: &[Migration]) -> Result<()> {
+    for migration in migrations {
+        let transaction = connection.transaction()?;
+        // Violations present before the migration are left for the doctor command to repair
+        let previous_violations = count_violations(&transaction)?;
+        transaction.execute_batch(migration.sql)?;
+        let violations = count_violations(&transaction)?;
+        Error::check_violations(
+            &format!("Migration to version {}", migration.version),
+            violations.saturating_sub(previous_violations),
+        )?;
+        set_version(&transaction, migration.version)?;
+        transaction.commit()?;
+    }
+    Ok(())
+}
+
+fn count_violations(connection: &Connection) -> Result<usize> {
+    Ok(
+        connection.query_row("SELECT COUNT(*) FROM pragma_foreign_key_check", [], |row| {
+            row.get(0)
+        })?,
+    )
+}
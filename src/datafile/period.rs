@@ -0,0 +1,86 @@
+//! Calendar-aligned periods of the habit statistics.
+use chrono::{Datelike, Days, NaiveDate, Weekday};
+
+/// Calendar unit the habit statistics are calculated over.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalendarPeriod {
+    Week,
+    Month,
+    Quarter,
+    Year,
+    FiveYears,
+}
+
+impl CalendarPeriod {
+    /// Returns the first day of the period containing the date. Weeks start on `week_start`.
+    /// Periods reaching beyond the supported dates are cut at them.
+    pub fn first_day(&self, date: &NaiveDate, week_start: Weekday) -> NaiveDate {
+        let first_day = match self {
+            CalendarPeriod::Week => {
+                date.checked_sub_days(Days::new(days_since(date.weekday(), week_start)))
+            }
+            CalendarPeriod::Month => date.with_day(1),
+            CalendarPeriod::Quarter => {
+                NaiveDate::from_ymd_opt(date.year(), date.month0() / 3 * 3 + 1, 1)
+            }
+            CalendarPeriod::Year => NaiveDate::from_ymd_opt(date.year(), 1, 1),
+            CalendarPeriod::FiveYears => {
+                NaiveDate::from_ymd_opt(date.year().div_euclid(5) * 5, 1, 1)
+            }
+        };
+        first_day.unwrap_or(NaiveDate::MIN)
+    }
+
+    /// Returns the label of the period starting on the day,
+    /// e.g. "2025-W14", "2025-03", "2025-Q1", "2025" or "2025-2029".
+    /// Weeks are labelled with the ISO week their Thursday falls into,
+    /// which is the ISO week itself when the weeks start on Monday.
+    pub fn label(&self, first_day: &NaiveDate) -> String {
+        match self {
+            CalendarPeriod::Week => {
+                let offset = days_since(Weekday::Thu, first_day.weekday());
+                let thursday = first_day
+                    .checked_add_days(Days::new(offset))
+                    .unwrap_or(*first_day);
+                let week = thursday.iso_week();
+                format!("{}-W{:02}", week.year(), week.week())
+            }
+            CalendarPeriod::Month => format!("{}-{:02}", first_day.year(), first_day.month()),
+            CalendarPeriod::Quarter => {
+                format!("{}-Q{}", first_day.year(), first_day.month0() / 3 + 1)
+            }
+            CalendarPeriod::Year => format!("{}", first_day.year()),
+            CalendarPeriod::FiveYears => {
+                format!("{}-{}", first_day.year(), first_day.year() + 4)
+            }
+        }
+    }
+}
+
+/// Calculates calendar-aligned date ranges: the period containing `from_date`
+/// and the `iters - 1` periods before it. Like the ranges of `get_date_ranges`,
+/// each range is specified by its last and first day, the latest range coming first.
+/// The period containing `from_date` is partial, it ends on `from_date`.
+pub fn get_calendar_date_ranges(
+    from_date: &NaiveDate,
+    period: CalendarPeriod,
+    week_start: Weekday,
+    iters: usize,
+) -> Vec<(NaiveDate, NaiveDate)> {
+    let mut ranges = Vec::with_capacity(iters);
+    let mut last_day = *from_date;
+    for _ in 0..iters {
+        let first_day = period.first_day(&last_day, week_start);
+        ranges.push((last_day, first_day));
+        let Some(previous_day) = first_day.pred_opt() else {
+            break;
+        };
+        last_day = previous_day;
+    }
+    ranges
+}
+
+/// Returns the number of days from the latest `earlier` weekday to `later`.
+fn days_since(later: Weekday, earlier: Weekday) -> u64 {
+    u64::from((7 + later.num_days_from_monday() - earlier.num_days_from_monday()) % 7)
+}
@@ -0,0 +1,495 @@
+//! Storage-independent interface of the habit data.
+use super::{
+    Category, CategoryId, CategoryStats, DayEntry, DayRecord, EntryStatus, ExcusedRange, Polarity,
+    Result, Schedule, SuccessfulUpdate, Target,
+};
+use chrono::{Days, NaiveDate, TimeDelta};
+use std::{
+    collections::{BTreeMap, HashMap},
+    ops::RangeInclusive,
+};
+
+/// Number of days read at once by `DiaryStore::iter_days`.
+const DAY_BATCH_SIZE: u64 = 64;
+
+/// Storage of the habit data: the categories, the daily entries and the statistics over them.
+/// The derived data is calculated from the entries by default, but the implementations
+/// may provide more efficient ways to query it.
+pub trait DiaryStore {
+    /// Returns whether the data is opened in read-only mode.
+    fn is_read_only(&self) -> bool;
+
+    /// Returns all categories including the hidden ones, in display order.
+    fn get_categories(&self) -> Result<Vec<Category>>;
+
+    /// Returns the frequency targets of all categories that have one, keyed by category ID.
+    fn get_targets(&self) -> Result<HashMap<CategoryId, Target>>;
+
+    /// Returns the records of the days between the two dates (inclusive) in chronological order,
+    /// including the days that were not tracked.
+    fn get_days(&self, from: &NaiveDate, until: &NaiveDate) -> Result<Vec<DayRecord>>;
+
+    /// Returns all numeric values recorded between the two dates (inclusive)
+    /// as `(date, category ID, value)` tuples.
+    fn get_values(
+        &self,
+        from: &NaiveDate,
+        until: &NaiveDate,
+    ) -> Result<Vec<(NaiveDate, CategoryId, f64)>>;
+
+    /// Returns the status of the entries between the two dates (inclusive)
+    /// as (date, category id, status) tuples.
+    fn get_statuses(
+        &self,
+        from: &NaiveDate,
+        until: &NaiveDate,
+    ) -> Result<Vec<(NaiveDate, CategoryId, EntryStatus)>>;
+
+    /// Returns all excused ranges ordered by their first day.
+    fn get_excused_ranges(&self) -> Result<Vec<ExcusedRange>>;
+
+    /// Returns the note attached to the specified date.
+    fn get_note(&self, date: &NaiveDate) -> Result<Option<String>>;
+
+    /// Returns whether no day was tracked yet.
+    fn is_empty(&self) -> Result<bool>;
+
+    /// Returns the first and the last tracked day. Fails if no day was tracked yet.
+    fn get_date_range(&self) -> Result<(NaiveDate, NaiveDate)>;
+
+    /// Replaces the checked categories of the specified date.
+    /// The numeric values and the statuses of the categories that stay checked are kept.
+    fn update_data(&mut self, date: &NaiveDate, new_row: &[CategoryId])
+    -> Result<SuccessfulUpdate>;
+
+    /// Records a numeric value for the category on the specified date.
+    /// The category is checked on that date if it was not checked before.
+    /// Passing `None` keeps the category checked, but removes its value.
+    fn set_value(
+        &mut self,
+        date: &NaiveDate,
+        category_id: CategoryId,
+        value: Option<f64>,
+    ) -> Result<()>;
+
+    /// Sets the status of an entry. When `status` is `None`, the entry is removed together with its value.
+    fn set_status(
+        &mut self,
+        date: &NaiveDate,
+        category_id: CategoryId,
+        status: Option<EntryStatus>,
+    ) -> Result<()>;
+
+    /// Attaches a note to the specified date, replacing the previous one.
+    /// An empty note removes the note from the date.
+    fn set_note(&mut self, date: &NaiveDate, note: &str) -> Result<()>;
+
+    /// Marks the days between the two dates (inclusive) as excused or not excused,
+    /// either for a single category or for the whole diary (when `category_id` is `None`).
+    /// Overlapping and adjacent ranges are merged.
+    fn set_excused(
+        &mut self,
+        category_id: Option<CategoryId>,
+        from: &NaiveDate,
+        until: &NaiveDate,
+        excused: bool,
+    ) -> Result<()>;
+
+    /// Returns the visible categories in display order.
+    fn get_header(&self) -> Result<Vec<Category>> {
+        Ok(self
+            .get_categories()?
+            .into_iter()
+            .filter(|category| !category.hidden)
+            .collect())
+    }
+
+    /// Returns the records of the days in the range in chronological order, reading them in batches.
+    /// The iteration stops after the first error.
+    fn iter_days(
+        &self,
+        range: RangeInclusive<NaiveDate>,
+    ) -> Box<dyn Iterator<Item = Result<DayRecord>> + '_> {
+        let (from, until) = range.into_inner();
+        let mut next_from = (from <= until).then_some(from);
+        let batches = std::iter::from_fn(move || {
+            let batch_from = next_from?;
+            let batch_until = batch_from
+                .checked_add_days(Days::new(DAY_BATCH_SIZE - 1))
+                .map_or(until, |date| date.min(until));
+            next_from = batch_until.succ_opt().filter(|date| *date <= until);
+            let batch = self.get_days(&batch_from, &batch_until);
+            if batch.is_err() {
+                next_from = None;
+            }
+            Some(batch)
+        });
+        Box::new(batches.flat_map(|batch| match batch {
+            Ok(records) => records.into_iter().map(Ok).collect(),
+            Err(error) => vec![Err(error)],
+        }))
+    }
+
+    /// Returns the checked visible categories of the specified date, or `None` if it was not tracked.
+    fn get_row(&self, date: &NaiveDate) -> Result<Option<Vec<CategoryId>>> {
+        let visible: Vec<CategoryId> = self
+            .get_header()?
+            .iter()
+            .map(|category| category.id)
+            .collect();
+        let Some(record) = self.get_days(date, date)?.pop() else {
+            return Ok(None);
+        };
+        Ok(record.entries.map(|entries| {
+            entries
+                .iter()
+                .map(|entry| entry.category_id)
+                .filter(|id| visible.contains(id))
+                .collect()
+        }))
+    }
+
+    /// Returns the numeric value recorded for the category on the specified date.
+    fn get_value(&self, date: &NaiveDate, category_id: CategoryId) -> Result<Option<f64>> {
+        Ok(self
+            .get_values(date, date)?
+            .into_iter()
+            .find(|(_, id, _)| *id == category_id)
+            .map(|(_, _, value)| value))
+    }
+
+    /// Replaces the checked categories of several dates.
+    fn update_data_batch(&mut self, new_items: &[(NaiveDate, Vec<CategoryId>)]) -> Result<()> {
+        for (date, new_row) in new_items {
+            self.update_data(date, new_row)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces the entries of several dates, including their statuses and values.
+    /// The categories that are not listed are unchecked on these dates.
+    fn update_entries_batch(&mut self, new_items: &[(NaiveDate, Vec<DayEntry>)]) -> Result<()> {
+        for (date, entries) in new_items {
+            let new_row: Vec<CategoryId> = entries.iter().map(|entry| entry.category_id).collect();
+            self.update_data(date, &new_row)?;
+            for entry in entries {
+                self.set_status(date, entry.category_id, Some(entry.status))?;
+                self.set_value(date, entry.category_id, entry.value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the days between the two dates (inclusive) that were not tracked, in chronological order.
+    /// Without `from`, the days are counted from the first tracked day.
+    /// The days when the whole diary is excused are not missing.
+    fn get_missing_dates(
+        &self,
+        from: &Option<NaiveDate>,
+        until: &NaiveDate,
+    ) -> Result<Vec<NaiveDate>> {
+        if self.is_empty()? {
+            return Ok(vec![]);
+        }
+        let from = match from {
+            Some(date) => *date,
+            None => self.get_date_range()?.0,
+        };
+        if from > *until {
+            return Ok(vec![]);
+        }
+        Ok(self
+            .get_days(&from, until)?
+            .into_iter()
+            .filter(|record| record.entries.is_none() && !record.excused)
+            .map(|record| record.date)
+            .collect())
+    }
+
+    /// Calculates the statistics of the visible categories in each of the date ranges.
+    /// Each range is specified by its last and first day, like the ones of `get_date_ranges`.
+    fn calculate_data_counts_per_iter(
+        &self,
+        date_ranges: &[(NaiveDate, NaiveDate)],
+    ) -> Result<Vec<Vec<CategoryStats>>> {
+        let categories = self.get_header()?;
+        let mut result =
+            count_scheduled_days(&categories, &self.get_excused_ranges()?, date_ranges);
+        let (Some(earliest), Some(latest)) = (
+            date_ranges.iter().map(|(_, to)| *to).min(),
+            date_ranges.iter().map(|(from, _)| *from).max(),
+        ) else {
+            return Ok(result);
+        };
+        let values: HashMap<(NaiveDate, CategoryId), f64> = self
+            .get_values(&earliest, &latest)?
+            .into_iter()
+            .map(|(date, id, value)| ((date, id), value))
+            .collect();
+        // Only the entries on the scheduled days are counted
+        for (date, category_id, status) in self.get_statuses(&earliest, &latest)? {
+            let Some(category_idx) = categories.iter().position(|category| {
+                category.id == category_id && category.schedule.is_scheduled(&date)
+            }) else {
+                continue;
+            };
+            for (period_idx, (from, to)) in date_ranges.iter().enumerate() {
+                if date < *to || date > *from {
+                    continue;
+                }
+                let stats = &mut result[period_idx][category_idx];
+                match status {
+                    EntryStatus::Skipped => stats.skipped_count += 1,
+                    EntryStatus::Partial => {
+                        stats.count += 1;
+                        stats.partial_count += 1;
+                    }
+                    EntryStatus::Done => stats.count += 1,
+                }
+                if let Some(value) = values.get(&(date, category_id))
+                    && status != EntryStatus::Skipped
+                {
+                    stats.sum += value;
+                    stats.value_count += 1;
+                }
+            }
+        }
+        Ok(result)
+    }
+
+    /// Returns the most frequent compositions of the visible categories occurring on the same day
+    /// between the two dates (inclusive), with the number of days they occurred on.
+    /// The compositions only include the habits to build, the habits to avoid are left out.
+    /// The skipped entries are not included. Without `from`, all days until `until` are considered.
+    fn get_most_frequent_daily_data(
+        &self,
+        from: &Option<NaiveDate>,
+        until: &NaiveDate,
+        max_count: Option<usize>,
+    ) -> Result<Vec<(Vec<CategoryId>, usize)>> {
+        let visible: Vec<CategoryId> = self
+            .get_header()?
+            .iter()
+            .filter(|category| category.polarity == Polarity::Build)
+            .map(|category| category.id)
+            .collect();
+        let mut days: BTreeMap<NaiveDate, Vec<CategoryId>> = BTreeMap::new();
+        for (date, category_id, status) in
+            self.get_statuses(&from.unwrap_or(NaiveDate::MIN), until)?
+        {
+            if status != EntryStatus::Skipped && visible.contains(&category_id) {
+                days.entry(date).or_default().push(category_id);
+            }
+        }
+        let mut counts: HashMap<Vec<CategoryId>, usize> = HashMap::new();
+        for (_, mut category_ids) in days {
+            category_ids.sort();
+            *counts.entry(category_ids).or_default() += 1;
+        }
+        let mut result: Vec<(Vec<CategoryId>, usize)> = counts.into_iter().collect();
+        result.sort_by(|(a_ids, a_count), (b_ids, b_count)| {
+            b_count.cmp(a_count).then_with(|| a_ids.cmp(b_ids))
+        });
+        if let Some(max_count) = max_count {
+            result.truncate(max_count);
+        }
+        Ok(result)
+    }
+
+    /// Returns the number of days passed from the last occurrence of the category
+    /// until the specified date, or `None` if the category has not occurred yet.
+    fn get_days_since_last_occurrence(
+        &self,
+        category_id: CategoryId,
+        until: &NaiveDate,
+    ) -> Result<Option<usize>> {
+        Ok(self
+            .get_statuses(&NaiveDate::MIN, until)?
+            .into_iter()
+            .filter(|(_, id, status)| *id == category_id && *status != EntryStatus::Skipped)
+            .map(|(date, _, _)| (*until - date).num_days() as usize)
+            .min())
+    }
+
+    /// Returns the length of the streak of the category that lasts until the specified date.
+    /// For habits to build, it is the number of consecutive scheduled days the category occurred on,
+    /// where the excused and skipped days do not break the streak. Partially done days extend it.
+    /// When the category has not occurred on `until` yet, the streak ending on the previous day counts.
+    /// For habits to avoid, it is the number of days since the category last occurred,
+    /// or since the first tracked day.
+    fn get_streak(&self, category_id: CategoryId, until: &NaiveDate) -> Result<usize> {
+        let (polarity, schedule) = get_polarity_and_schedule(self, category_id)?;
+        if polarity == Polarity::Avoid {
+            return get_days_without_occurrence(self, category_id, until);
+        }
+        let mut entries = self
+            .get_statuses(&NaiveDate::MIN, until)?
+            .into_iter()
+            .filter(|(_, id, _)| *id == category_id)
+            .map(|(date, _, status)| Ok((date, status)))
+            .collect::<Vec<_>>();
+        entries.reverse();
+        count_streak(
+            entries,
+            &schedule,
+            &self.get_excused_ranges()?,
+            category_id,
+            until,
+        )
+    }
+}
+
+/// Returns the polarity and the schedule of the category, or the defaults if it does not exist.
+pub(super) fn get_polarity_and_schedule<S: DiaryStore + ?Sized>(
+    store: &S,
+    category_id: CategoryId,
+) -> Result<(Polarity, Schedule)> {
+    Ok(store
+        .get_categories()?
+        .into_iter()
+        .find(|category| category.id == category_id)
+        .map(|category| (category.polarity, category.schedule))
+        .unwrap_or_default())
+}
+
+/// Returns the number of days since the category last occurred, or since the first tracked day.
+pub(super) fn get_days_without_occurrence<S: DiaryStore + ?Sized>(
+    store: &S,
+    category_id: CategoryId,
+    until: &NaiveDate,
+) -> Result<usize> {
+    if let Some(days) = store.get_days_since_last_occurrence(category_id, until)? {
+        return Ok(days);
+    }
+    if store.is_empty()? {
+        return Ok(0);
+    }
+    let (first_date, _) = store.get_date_range()?;
+    Ok(((*until - first_date).num_days() + 1).max(0) as usize)
+}
+
+/// Counts the streak of a habit to build from its entries until `until`, the latest entry first.
+pub(super) fn count_streak(
+    entries: impl IntoIterator<Item = Result<(NaiveDate, EntryStatus)>>,
+    schedule: &Schedule,
+    excused_ranges: &[ExcusedRange],
+    category_id: CategoryId,
+    until: &NaiveDate,
+) -> Result<usize> {
+    let mut expected_date = *until;
+    let mut streak = 0;
+    for entry in entries {
+        let (date, status) = entry?;
+        // Days when the category is not scheduled or excused do not break the streak
+        while date < expected_date {
+            let pending_today = streak == 0 && expected_date == *until;
+            let excused = excused_ranges
+                .iter()
+                .any(|range| range.covers(&expected_date, category_id));
+            if schedule.is_scheduled(&expected_date) && !excused && !pending_today {
+                return Ok(streak);
+            }
+            expected_date -= TimeDelta::days(1);
+        }
+        // Skipped days do not break the streak either, but they do not extend it
+        if status != EntryStatus::Skipped {
+            streak += 1;
+        }
+        // The earliest supported date ends the streak
+        let Some(previous_date) = date.pred_opt() else {
+            break;
+        };
+        expected_date = previous_date;
+    }
+    Ok(streak)
+}
+
+/// Creates the statistics of the categories in each date range with the number of
+/// scheduled and excused days filled in.
+pub(super) fn count_scheduled_days(
+    categories: &[Category],
+    excused_ranges: &[ExcusedRange],
+    date_ranges: &[(NaiveDate, NaiveDate)],
+) -> Vec<Vec<CategoryStats>> {
+    date_ranges
+        .iter()
+        .map(|(until, from)| {
+            categories
+                .iter()
+                .map(|category| {
+                    let mut stats = CategoryStats::default();
+                    let mut scheduled_days = category.schedule.count_scheduled_days(from, until);
+                    for (excused_from, excused_until) in
+                        merge_excused_ranges(excused_ranges, category.id, from, until)
+                    {
+                        stats.excused_days +=
+                            (excused_until - excused_from).num_days() as usize + 1;
+                        scheduled_days -= category
+                            .schedule
+                            .count_scheduled_days(&excused_from, &excused_until);
+                    }
+                    stats.scheduled_days = scheduled_days;
+                    stats
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Returns the days between `from` and `until` (inclusive) when the category is excused,
+/// as sorted ranges that do not overlap.
+fn merge_excused_ranges(
+    excused_ranges: &[ExcusedRange],
+    category_id: CategoryId,
+    from: &NaiveDate,
+    until: &NaiveDate,
+) -> Vec<(NaiveDate, NaiveDate)> {
+    let mut ranges: Vec<(NaiveDate, NaiveDate)> = excused_ranges
+        .iter()
+        .filter(|range| range.category_id.is_none_or(|id| id == category_id))
+        .map(|range| (range.from.max(*from), range.until.min(*until)))
+        .filter(|(from, until)| from <= until)
+        .collect();
+    ranges.sort();
+    let mut merged: Vec<(NaiveDate, NaiveDate)> = vec![];
+    for (from, until) in ranges {
+        match merged.last_mut() {
+            Some((_, last_until)) if from <= *last_until => *last_until = until.max(*last_until),
+            _ => merged.push((from, until)),
+        }
+    }
+    merged
+}
+
+/// Returns the ranges replacing the `affected` ones, which overlap or are adjacent to
+/// the days between `from` and `until` (inclusive), after marking these days as excused or not.
+pub(super) fn split_excused_ranges(
+    affected: &[(NaiveDate, NaiveDate)],
+    from: &NaiveDate,
+    until: &NaiveDate,
+    excused: bool,
+) -> Vec<(NaiveDate, NaiveDate)> {
+    let one_day = TimeDelta::days(1);
+    let mut new_ranges = vec![];
+    if excused {
+        let new_from = affected
+            .iter()
+            .map(|(range_from, _)| *range_from)
+            .fold(*from, NaiveDate::min);
+        let new_until = affected
+            .iter()
+            .map(|(_, range_until)| *range_until)
+            .fold(*until, NaiveDate::max);
+        new_ranges.push((new_from, new_until));
+    } else {
+        for (range_from, range_until) in affected {
+            if range_from < from {
+                new_ranges.push((*range_from, (*range_until).min(*from - one_day)));
+            }
+            if range_until > until {
+                new_ranges.push(((*range_from).max(*until + one_day), *range_until));
+            }
+        }
+    }
+    new_ranges
+}
@@ -1,7 +1,14 @@
-use anyhow::{Result, bail};
-use clap::Parser;
-use genee::datafile;
-use std::path::{Path, PathBuf};
+use anyhow::{Context, Result, bail};
+use chrono::{Local, NaiveDate};
+use clap::{Parser, Subcommand, ValueEnum};
+use genee::datafile::{self, DiaryStore};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::Write,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    str::FromStr,
+};
 
 mod configuration;
 mod ui;
@@ -23,6 +30,30 @@ struct CliOptions {
     #[arg(short = 'f', long)]
     list_most_frequent_days: Option<usize>,
 
+    /// Alignment of the periods of the habit histogram.
+    /// When not provided, its value is loaded from persistent configuration file.
+    #[arg(long, value_enum)]
+    period_alignment: Option<PeriodAlignment>,
+
+    /// First day of the calendar weeks, e.g. "mon" or "sun".
+    /// When not provided, its value is loaded from persistent configuration file.
+    #[arg(long)]
+    week_start: Option<chrono::Weekday>,
+
+    /// Opens the diary file without modifying it. No backup is made and edits are refused.
+    #[arg(long)]
+    read_only: bool,
+
+    /// Directory of the backups of the diary file. Defaults to a directory next to the diary file.
+    /// When not provided, its value is loaded from persistent configuration file.
+    #[arg(long)]
+    backup_dir: Option<PathBuf>,
+
+    /// Specifies the number of backups kept, the oldest ones are deleted beyond it.
+    /// Zero disables the automatic backups. When not provided, its value is loaded from persistent configuration file.
+    #[arg(long)]
+    backup_count: Option<usize>,
+
     #[command(subcommand)]
     command: Option<Command>,
 }
@@ -34,7 +65,13 @@ enum Command {
 
     /// Provide a comma separated list of habit categories. A new diary file is created at the specified
     /// --datafile path.
-    New { category_list: String },
+    New {
+        category_list: String,
+
+        /// Encrypts the diary file with a passphrase. Requires the "encryption" feature.
+        #[arg(long)]
+        encrypt: bool,
+    },
 
     /// Saves the specified options to persistent configuration.
     SaveConfig,
@@ -44,11 +81,510 @@ enum Command {
 
     /// Hides a category.
     HideCategory { name: String },
+
+    /// Attaches a note to a day. An empty note removes the existing note.
+    Note {
+        text: String,
+
+        /// The day to attach the note to. Defaults to today.
+        #[arg(long)]
+        date: Option<NaiveDate>,
+    },
+
+    /// Manages the categories of the diary.
+    Category {
+        #[command(subcommand)]
+        command: CategoryCommand,
+    },
+
+    /// Manages the frequency targets of the categories.
+    Target {
+        #[command(subcommand)]
+        command: TargetCommand,
+    },
+
+    /// Updates the datafile to the latest schema version. A backup is made before the update.
+    Migrate {
+        /// Only list the pending migration steps without performing them.
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Manages the excused days, e.g. vacations or illnesses, which do not count as missed.
+    Excuse {
+        #[command(subcommand)]
+        command: ExcuseCommand,
+    },
+
+    /// Checks the integrity of the datafile and lists the problems found.
+    Doctor {
+        /// Repairs the problems found. A backup is made before the repair.
+        #[arg(long)]
+        fix: bool,
+    },
+
+    /// Manages the backups of the datafile.
+    Backup {
+        #[command(subcommand)]
+        command: BackupCommand,
+    },
+
+    /// Changes the passphrase of the datafile, encrypting it if it is not encrypted yet.
+    /// An empty passphrase removes the encryption. Requires the "encryption" feature.
+    Rekey,
+
+    /// Exports the diary with one row per date and one column per category.
+    /// Done entries are written as 1 or their value, missing entries as 0.
+    /// The JSON format contains the complete datafile instead.
+    Export {
+        /// Format of the exported data.
+        #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+
+        /// The first day to export. Defaults to the first tracked day.
+        #[arg(long)]
+        from: Option<NaiveDate>,
+
+        /// The last day to export. Defaults to the last tracked day.
+        #[arg(long)]
+        until: Option<NaiveDate>,
+
+        /// Also export the hidden categories.
+        #[arg(long)]
+        include_hidden: bool,
+
+        /// How the days that were not tracked are written.
+        #[arg(long, value_enum, default_value_t = UntrackedArg::Empty)]
+        untracked: UntrackedArg,
+
+        /// File to write the export to. Defaults to the standard output.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Imports habit data from other sources.
+    Import {
+        #[command(subcommand)]
+        command: ImportCommand,
+    },
+}
+
+impl Command {
+    /// Returns whether the command modifies the datafile.
+    fn is_editing(&self) -> bool {
+        match self {
+            Command::ListConfig | Command::SaveConfig => false,
+            Command::Category {
+                command: CategoryCommand::List,
+            } => false,
+            Command::Target {
+                command: TargetCommand::Check,
+            } => false,
+            Command::Migrate { dry_run } => !dry_run,
+            Command::Excuse {
+                command: ExcuseCommand::List,
+            } => false,
+            Command::Doctor { fix } => *fix,
+            Command::Export { .. } => false,
+            Command::Import { command } => !command.is_dry_run(),
+            Command::Backup {
+                command: BackupCommand::List | BackupCommand::Create,
+            } => false,
+            _ => true,
+        }
+    }
+}
+
+#[derive(Subcommand, Clone)]
+enum CategoryCommand {
+    /// Lists all categories in display order, including the hidden ones.
+    List,
+
+    /// Renames a category.
+    Rename { name: String, new_name: String },
+
+    /// Changes the descriptive properties of a category. Pass an empty value to clear a property.
+    Edit {
+        name: String,
+
+        /// Long, human readable name of the category.
+        #[arg(long)]
+        display_name: Option<String>,
+
+        /// Free-text description of the category.
+        #[arg(long)]
+        description: Option<String>,
+
+        /// Emoji or symbol displayed for the checked entries of the category.
+        #[arg(long)]
+        symbol: Option<String>,
+
+        /// Colour of the category, either a colour name like "lightblue" or an RGB value like "#ff8800".
+        #[arg(long)]
+        color: Option<String>,
+
+        /// Whether the category is a habit to build or to avoid.
+        #[arg(long)]
+        polarity: Option<PolarityArg>,
+    },
+
+    /// Sets the days on which a category is expected to occur.
+    Schedule {
+        name: String,
+
+        /// "daily", a comma separated list of weekdays like "mon,wed,fri", or "every:N" for every N days.
+        schedule: String,
+
+        /// First scheduled day of an "every:N" schedule. Defaults to today.
+        #[arg(long)]
+        from: Option<NaiveDate>,
+    },
+
+    /// Provide a comma separated list of categories to move them to the front of the display order.
+    Reorder { category_list: String },
+
+    /// Moves all entries of the source category to the target category, then deletes the source.
+    Merge { source: String, target: String },
+
+    /// Deletes a category together with all of its entries.
+    Delete {
+        name: String,
+
+        /// Do not ask for confirmation.
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+/// Alignment of the periods of the habit histogram.
+#[derive(ValueEnum, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+enum PeriodAlignment {
+    /// Periods of 7, 30, 90, 365 or 1825 days ending on the selected day.
+    #[default]
+    Rolling,
+
+    /// Calendar weeks, months, quarters, years or five years containing the selected day.
+    Calendar,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum ExportFormat {
+    /// Comma separated values.
+    Csv,
+
+    /// Tab separated values.
+    Tsv,
+
+    /// The complete content of the datafile, which can be imported with "import json".
+    Json,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum UntrackedArg {
+    /// Empty cells.
+    Empty,
+
+    /// Zeros, like a tracked day without entries.
+    Zero,
+
+    /// No row.
+    Omit,
+}
+
+impl From<UntrackedArg> for datafile::UntrackedDays {
+    fn from(value: UntrackedArg) -> Self {
+        match value {
+            UntrackedArg::Empty => datafile::UntrackedDays::Empty,
+            UntrackedArg::Zero => datafile::UntrackedDays::Zero,
+            UntrackedArg::Omit => datafile::UntrackedDays::Omit,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum PolarityArg {
+    /// More occurrences are better.
+    Build,
+
+    /// Fewer occurrences are better.
+    Avoid,
+}
+
+impl From<PolarityArg> for datafile::Polarity {
+    fn from(value: PolarityArg) -> Self {
+        match value {
+            PolarityArg::Build => datafile::Polarity::Build,
+            PolarityArg::Avoid => datafile::Polarity::Avoid,
+        }
+    }
+}
+
+#[derive(Subcommand, Clone)]
+enum TargetCommand {
+    /// Sets the frequency target of a category, e.g. at least 4 times per 7 days.
+    Set {
+        name: String,
+
+        /// The category should occur at least this many times per period.
+        #[arg(long, conflicts_with = "at_most", required_unless_present = "at_most")]
+        at_least: Option<usize>,
+
+        /// The category should occur at most this many times per period.
+        #[arg(long)]
+        at_most: Option<usize>,
+
+        /// Length of the period in days.
+        #[arg(long, default_value_t = 7)]
+        days: usize,
+    },
+
+    /// Removes the frequency target of a category.
+    Clear { name: String },
+
+    /// Reports whether the categories are on track with their targets in the current period.
+    Check,
+}
+
+#[derive(Subcommand, Clone)]
+enum ExcuseCommand {
+    /// Excuses the days between two dates (inclusive).
+    Add(ExcuseRangeArgs),
+
+    /// Removes the excuse from the days between two dates (inclusive).
+    Remove(ExcuseRangeArgs),
+
+    /// Lists the excused date ranges.
+    List,
+}
+
+#[derive(Subcommand, Clone)]
+enum BackupCommand {
+    /// Lists the backups of the datafile, the newest first.
+    List,
+
+    /// Backs up the datafile.
+    Create,
+
+    /// Replaces the datafile with a backup, after checking the integrity of the backup.
+    /// The datafile is backed up before being replaced.
+    Restore {
+        /// Identifier of the backup, as listed by "backup list".
+        id: String,
+
+        /// Do not ask for confirmation.
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand, Clone)]
+enum ImportCommand {
+    /// Imports a CSV file with a date column and one column per category, e.g. exported from a spreadsheet.
+    /// The columns are mapped to the categories with the same name, unless mapped otherwise with --map.
+    Csv(CsvImportArgs),
+
+    /// Replaces the content of the datafile with a JSON file written by "export --format json".
+    /// The datafile is created if it does not exist.
+    Json {
+        /// Path of the JSON file.
+        file: PathBuf,
+
+        /// Do not ask for confirmation.
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Imports a backup of the Loop Habit Tracker app.
+    /// The habits are mapped to the categories with the same name, which are created if they do not exist.
+    /// The categories created for archived habits are hidden.
+    Loop {
+        /// Path of the backup, e.g. "Loop Habits Backup 2025-01-31.db".
+        file: PathBuf,
+
+        /// How the days that are already tracked in the diary are handled.
+        #[arg(long, value_enum, default_value_t = ConflictArg::Skip)]
+        on_conflict: ConflictArg,
+
+        /// Only report what would be imported without modifying the datafile.
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Imports the CSV export of the Daylio app. Its activities are imported as categories.
+    /// The category of each activity that is not mapped with --map or --mapping-file is asked for.
+    Daylio(ActivityImportArgs),
+
+    /// Imports the JSON data export of the Habitica app. Its dailies are imported as categories.
+    /// The category of each daily that is not mapped with --map or --mapping-file is asked for.
+    Habitica(ActivityImportArgs),
 }
 
-fn main() -> Result<()> {
+impl ImportCommand {
+    fn is_dry_run(&self) -> bool {
+        match self {
+            ImportCommand::Csv(args) => args.dry_run,
+            ImportCommand::Json { .. } => false,
+            ImportCommand::Loop { dry_run, .. } => *dry_run,
+            ImportCommand::Daylio(args) | ImportCommand::Habitica(args) => args.dry_run,
+        }
+    }
+}
+
+#[derive(clap::Args, Clone)]
+struct CsvImportArgs {
+    /// Path of the CSV file.
+    file: PathBuf,
+
+    /// Maps a column to a category, e.g. "Gym=GYM". Can be repeated.
+    #[arg(long = "map", value_name = "COLUMN=CATEGORY", value_parser = parse_mapping)]
+    mapping: Vec<(String, String)>,
+
+    /// Header of the column holding the dates. Defaults to the first column.
+    #[arg(long)]
+    date_column: Option<String>,
+
+    /// Format of the dates, e.g. "%d/%m/%Y".
+    #[arg(long, default_value = "%Y-%m-%d")]
+    date_format: String,
+
+    /// Separator of the columns.
+    #[arg(long, default_value_t = ',')]
+    delimiter: char,
+
+    /// Comma separated list of the cell values marking an entry as done, compared without regard to case.
+    #[arg(long, value_delimiter = ',', default_values = datafile::DEFAULT_TRUTHY_VALUES)]
+    truthy: Vec<String>,
+
+    /// Numeric cells at least this large mark an entry as done, e.g. minutes of practice.
+    /// Without a threshold, any positive number does. The numbers are imported as the values of the entries.
+    #[arg(long)]
+    threshold: Option<f64>,
+
+    /// How the days that are already tracked in the diary are handled.
+    #[arg(long, value_enum, default_value_t = ConflictArg::Skip)]
+    on_conflict: ConflictArg,
+
+    /// Only report what would be imported without modifying the datafile.
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(clap::Args, Clone)]
+struct ActivityImportArgs {
+    /// Path of the exported file.
+    file: PathBuf,
+
+    /// Maps an activity to a category, e.g. "Running=RUN". The category is created if it does not exist.
+    /// Mapping to "-" ignores the activity. Can be repeated.
+    #[arg(long = "map", value_name = "ACTIVITY=CATEGORY", value_parser = parse_mapping)]
+    mapping: Vec<(String, String)>,
+
+    /// File with one ACTIVITY=CATEGORY mapping per line, in the format of --map.
+    /// Empty lines and lines starting with "#" are ignored.
+    #[arg(long)]
+    mapping_file: Option<PathBuf>,
+
+    /// Do not ask for the categories of the activities that are not mapped,
+    /// map them to the categories with the same name instead.
+    #[arg(short, long)]
+    yes: bool,
+
+    /// How the days that are already tracked in the diary are handled.
+    #[arg(long, value_enum, default_value_t = ConflictArg::Skip)]
+    on_conflict: ConflictArg,
+
+    /// Only report what would be imported without modifying the datafile.
+    #[arg(long)]
+    dry_run: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+enum ConflictArg {
+    /// The tracked days are left unchanged.
+    Skip,
+
+    /// The entries of the imported categories are replaced, the other entries are kept.
+    Overwrite,
+
+    /// The imported entries are added to the existing ones.
+    Union,
+}
+
+impl From<ConflictArg> for datafile::ConflictPolicy {
+    fn from(value: ConflictArg) -> Self {
+        match value {
+            ConflictArg::Skip => datafile::ConflictPolicy::Skip,
+            ConflictArg::Overwrite => datafile::ConflictPolicy::Overwrite,
+            ConflictArg::Union => datafile::ConflictPolicy::Union,
+        }
+    }
+}
+
+#[derive(clap::Args, Clone)]
+struct ExcuseRangeArgs {
+    /// First day of the range.
+    from: NaiveDate,
+
+    /// Last day of the range. Defaults to the first day.
+    until: Option<NaiveDate>,
+
+    /// Excuse only this category instead of the whole diary.
+    #[arg(long)]
+    category: Option<String>,
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {:?}", error);
+            ExitCode::from(get_exit_code(&error))
+        }
+    }
+}
+
+/// Returns the exit code reporting the error, following the conventions of `sysexits.h`.
+fn get_exit_code(error: &anyhow::Error) -> u8 {
+    use datafile::Error;
+    use rusqlite::ErrorCode;
+    let Some(error) = error.chain().find_map(|e| e.downcast_ref::<Error>()) else {
+        return 1;
+    };
+    match error {
+        Error::EmptyDiary | Error::NonExistingCategory(_) | Error::BackupNotFound(_) => 66,
+        Error::CorruptData(_)
+        | Error::ForeignKeyViolation { .. }
+        | Error::UnsupportedVersion { .. }
+        | Error::InvalidBackup { .. }
+        | Error::InvalidInput(_) => 65,
+        Error::EncryptionUnsupported => 69,
+        Error::Io { .. } => 74,
+        Error::Locked { .. } => 75,
+        Error::PassphraseRequired
+        | Error::ReadOnly
+        | Error::WrongPassphrase
+        | Error::WrongBackupPassphrase(_)
+        | Error::MigrationRequired { .. } => 77,
+        Error::Csv(error) if error.is_io_error() => 74,
+        Error::Csv(_) => 65,
+        Error::Sqlite(error) => match error.sqlite_error_code() {
+            Some(ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked) => 75,
+            Some(ErrorCode::ReadOnly | ErrorCode::PermissionDenied) => 77,
+            Some(ErrorCode::NotADatabase | ErrorCode::DatabaseCorrupt) => 65,
+            Some(ErrorCode::CannotOpen | ErrorCode::SystemIoFailure) => 74,
+            _ => 70,
+        },
+        _ => 1,
+    }
+}
+
+fn run() -> Result<()> {
     let opt = handle_config()?;
     let datafile_path = opt.datafile.as_ref().unwrap();
+    if opt.read_only && opt.command.as_ref().is_some_and(Command::is_editing) {
+        return Err(datafile::Error::ReadOnly.into());
+    }
     match opt.command {
         Some(Command::ListConfig) => {
             let persistent_config = configuration::load_config()?;
@@ -58,17 +594,79 @@ fn main() -> Result<()> {
                 &persistent_config
             );
         }
-        Some(Command::New { ref category_list }) => {
-            create_new(datafile_path, category_list)?;
+        Some(Command::New {
+            ref category_list,
+            encrypt,
+        }) => {
+            create_new(datafile_path, category_list, encrypt)?;
         }
         Some(Command::SaveConfig) => {
             configuration::save_config_opt(&opt)?;
         }
         Some(Command::AddCategory { ref name }) => {
-            add_category(datafile_path, name)?;
+            add_category(&opt, name)?;
         }
         Some(Command::HideCategory { ref name }) => {
-            hide_category(datafile_path, name)?;
+            hide_category(&opt, name)?;
+        }
+        Some(Command::Note { ref text, date }) => {
+            set_note(&opt, text, date)?;
+        }
+        Some(Command::Category { ref command }) => {
+            handle_category_command(&opt, command)?;
+        }
+        Some(Command::Target { ref command }) => {
+            handle_target_command(&opt, command)?;
+        }
+        Some(Command::Migrate { dry_run }) => {
+            migrate(datafile_path, dry_run)?;
+        }
+        Some(Command::Excuse { ref command }) => {
+            handle_excuse_command(&opt, command)?;
+        }
+        Some(Command::Doctor { fix }) => {
+            doctor(&opt, fix)?;
+        }
+        Some(Command::Backup { ref command }) => {
+            handle_backup_command(&opt, command)?;
+        }
+        Some(Command::Rekey) => {
+            rekey(datafile_path)?;
+        }
+        Some(Command::Import { ref command }) => {
+            handle_import_command(&opt, command)?;
+        }
+        Some(Command::Export {
+            format: ExportFormat::Json,
+            from,
+            until,
+            ref output,
+            ..
+        }) => {
+            if from.is_some() || until.is_some() {
+                bail!(
+                    "The JSON export contains the complete datafile, --from and --until are not supported"
+                );
+            }
+            export_json(&opt, output.as_deref())?;
+        }
+        Some(Command::Export {
+            format,
+            from,
+            until,
+            include_hidden,
+            untracked,
+            ref output,
+        }) => {
+            let options = datafile::DelimitedExportOptions {
+                delimiter: match format {
+                    ExportFormat::Tsv => b'\t',
+                    _ => b',',
+                },
+                include_hidden,
+                untracked: untracked.into(),
+            };
+            export(&opt, from, until, &options, output.as_deref())?;
         }
         None => {
             ui::run_app(&opt)?;
@@ -78,6 +676,513 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+/// Opens the datafile, reporting the migrations performed on it.
+/// Unless the command leaves the datafile unmodified, a backup is made according to the options.
+fn open_datafile(opt: &CliOptions) -> Result<datafile::DiaryDataSqlite> {
+    let datafile = open_datafile_without_backup(opt)?;
+    let backup_policy = get_backup_policy(opt);
+    let editing = opt.command.as_ref().is_none_or(Command::is_editing);
+    if !opt.read_only && editing && backup_policy.count > 0 {
+        datafile.create_backup(opt.datafile.as_ref().unwrap(), &backup_policy)?;
+    }
+    Ok(datafile)
+}
+
+fn open_datafile_without_backup(opt: &CliOptions) -> Result<datafile::DiaryDataSqlite> {
+    let datafile_path = opt.datafile.as_ref().unwrap();
+    let key = read_passphrase(datafile_path)?;
+    if opt.read_only {
+        return Ok(datafile::open_datafile_read_only(
+            datafile_path,
+            key.as_deref(),
+        )?);
+    }
+    let (version, pending) = datafile::get_pending_migrations(datafile_path, key.as_deref())?;
+    if !pending.is_empty() {
+        println!(
+            "Updating the datafile from version {} to {}, a backup is saved to {}",
+            version,
+            pending.last().unwrap().version,
+            datafile::get_migration_backup_path(datafile_path, version).display()
+        );
+    }
+    Ok(datafile::open_datafile(datafile_path, key.as_deref())?)
+}
+
+/// Opens the datafile for an import. In a dry run, it is opened read-only,
+/// so it is neither updated to the latest schema version, locked nor backed up.
+fn open_datafile_for_import(opt: &CliOptions, dry_run: bool) -> Result<datafile::DiaryDataSqlite> {
+    if !dry_run {
+        return open_datafile(opt);
+    }
+    let datafile_path = opt.datafile.as_ref().unwrap();
+    let key = read_passphrase(datafile_path)?;
+    Ok(datafile::open_datafile_read_only(
+        datafile_path,
+        key.as_deref(),
+    )?)
+}
+
+/// Asks for the passphrase of the datafile if it is encrypted.
+fn read_passphrase(datafile_path: &Path) -> Result<Option<String>> {
+    if !datafile::is_encrypted(datafile_path)? {
+        return Ok(None);
+    }
+    prompt_passphrase(&format!("Passphrase of {}: ", datafile_path.display())).map(Some)
+}
+
+/// Asks for a new passphrase twice. An empty passphrase is returned as `None`.
+fn read_new_passphrase() -> Result<Option<String>> {
+    let passphrase = prompt_passphrase("New passphrase: ")?;
+    if passphrase != prompt_passphrase("Repeat the new passphrase: ")? {
+        bail!("The passphrases do not match");
+    }
+    Ok(Some(passphrase).filter(|passphrase| !passphrase.is_empty()))
+}
+
+#[cfg(feature = "encryption")]
+fn prompt_passphrase(prompt: &str) -> Result<String> {
+    Ok(rpassword::prompt_password(prompt)?)
+}
+
+#[cfg(not(feature = "encryption"))]
+fn prompt_passphrase(_prompt: &str) -> Result<String> {
+    bail!("genee was built without support for encrypted datafiles (the \"encryption\" feature)");
+}
+
+fn rekey(datafile_path: &Path) -> Result<()> {
+    let key = read_passphrase(datafile_path)?;
+    let new_key = read_new_passphrase()?;
+    datafile::rekey(datafile_path, key.as_deref(), new_key.as_deref())?;
+    if new_key.is_some() {
+        println!("The datafile is encrypted with the new passphrase");
+    } else {
+        println!("The encryption of the datafile is removed");
+    }
+    println!("The existing backups keep their previous passphrase");
+    Ok(())
+}
+
+fn get_backup_policy(opt: &CliOptions) -> datafile::BackupPolicy {
+    let datafile_path = opt.datafile.as_ref().unwrap();
+    datafile::BackupPolicy {
+        directory: opt
+            .backup_dir
+            .clone()
+            .unwrap_or_else(|| datafile::get_default_backup_dir(datafile_path)),
+        count: opt
+            .backup_count
+            .unwrap_or(configuration::DEFAULT_BACKUP_COUNT),
+    }
+}
+
+fn handle_backup_command(opt: &CliOptions, command: &BackupCommand) -> Result<()> {
+    let datafile_path = opt.datafile.as_ref().unwrap();
+    let backup_policy = get_backup_policy(opt);
+    match command {
+        BackupCommand::List => {
+            let backups = datafile::list_backups(datafile_path, &backup_policy)?;
+            if backups.is_empty() {
+                println!("No backups found in {}", backup_policy.directory.display());
+            }
+            for backup in backups {
+                println!(
+                    "{}  {}  {} bytes",
+                    backup.id,
+                    backup.created_at.format("%Y-%m-%d %H:%M:%S"),
+                    backup.size
+                );
+            }
+        }
+        BackupCommand::Create => {
+            let datafile = open_datafile_without_backup(opt)?;
+            let backup = datafile.create_backup(datafile_path, &backup_policy)?;
+            println!("Backup {} is saved to {}", backup.id, backup.path.display());
+        }
+        BackupCommand::Restore { id, yes } => {
+            if !yes
+                && !confirm(&format!(
+                    "Replace the datafile with backup {}? The datafile is backed up first.",
+                    id
+                ))?
+            {
+                println!("Aborted");
+                return Ok(());
+            }
+            let key = read_passphrase(datafile_path)?;
+            let restore = |backup_key: Option<&str>| {
+                datafile::restore_backup(
+                    datafile_path,
+                    &backup_policy,
+                    id,
+                    key.as_deref(),
+                    backup_key,
+                )
+            };
+            // Backups made before a rekey have a previous passphrase of the datafile
+            match restore(key.as_deref()) {
+                Err(datafile::Error::WrongBackupPassphrase(_)) => {
+                    let backup_key = prompt_passphrase(&format!("Passphrase of backup {}: ", id))?;
+                    restore(Some(&backup_key))?;
+                }
+                result => {
+                    result?;
+                }
+            }
+            println!("The datafile is restored from backup {}", id);
+        }
+    }
+    Ok(())
+}
+
+fn migrate(datafile_path: &Path, dry_run: bool) -> Result<()> {
+    let key = read_passphrase(datafile_path)?;
+    let (version, pending) = datafile::get_pending_migrations(datafile_path, key.as_deref())?;
+    if pending.is_empty() {
+        println!("The datafile is up to date (version {})", version);
+        return Ok(());
+    }
+    println!(
+        "The datafile is at version {}, pending migrations:",
+        version
+    );
+    for migration in pending {
+        println!("- {}: {}", migration.version, migration.description);
+    }
+    if !dry_run {
+        datafile::open_datafile(datafile_path, key.as_deref())?;
+        println!(
+            "The datafile is updated, a backup is saved to {}",
+            datafile::get_migration_backup_path(datafile_path, version).display()
+        );
+    }
+    Ok(())
+}
+
+fn export(
+    opt: &CliOptions,
+    from: Option<NaiveDate>,
+    until: Option<NaiveDate>,
+    options: &datafile::DelimitedExportOptions,
+    output: Option<&Path>,
+) -> Result<()> {
+    let datafile = open_datafile(opt)?;
+    let (from, until) = match (from, until) {
+        (Some(from), Some(until)) => (from, until),
+        _ => {
+            let (first, last) = datafile.get_date_range()?;
+            (from.unwrap_or(first), until.unwrap_or(last))
+        }
+    };
+    if until < from {
+        bail!("The last day of the range is earlier than the first day");
+    }
+    match output {
+        Some(path) => {
+            let file = std::fs::File::create(path)
+                .with_context(|| format!("Could not create {}", path.display()))?;
+            let row_count = datafile::export_delimited(&datafile, file, &from, &until, options)?;
+            println!("Exported {} days to {}", row_count, path.display());
+        }
+        None => {
+            datafile::export_delimited(
+                &datafile,
+                std::io::stdout().lock(),
+                &from,
+                &until,
+                options,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+fn export_json(opt: &CliOptions, output: Option<&Path>) -> Result<()> {
+    let datafile = open_datafile(opt)?;
+    let dump = datafile.dump()?;
+    let json = serde_json::to_string_pretty(&dump)?;
+    match output {
+        Some(path) => {
+            std::fs::write(path, json + "\n")
+                .with_context(|| format!("Could not write {}", path.display()))?;
+            println!(
+                "Exported {} categories and {} days to {}",
+                dump.categories.len(),
+                dump.days.len(),
+                path.display()
+            );
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+fn handle_import_command(opt: &CliOptions, command: &ImportCommand) -> Result<()> {
+    match command {
+        ImportCommand::Csv(args) => {
+            if !args.delimiter.is_ascii() {
+                bail!("The delimiter must be an ASCII character");
+            }
+            let mut datafile = open_datafile_for_import(opt, args.dry_run)?;
+            let categories = datafile.get_categories()?;
+            let mut mapping = vec![];
+            for (column, name) in &args.mapping {
+                match categories.iter().find(|category| &category.name == name) {
+                    Some(category) => mapping.push((column.clone(), category.id)),
+                    None => bail!("Category \"{}\" does not exist", name),
+                }
+            }
+            let options = datafile::DelimitedImportOptions {
+                delimiter: args.delimiter as u8,
+                date_column: args.date_column.clone(),
+                date_format: args.date_format.clone(),
+                mapping,
+                truthy_values: args.truthy.clone(),
+                threshold: args.threshold,
+                conflict_policy: args.on_conflict.into(),
+            };
+            let file = std::fs::File::open(&args.file)
+                .with_context(|| format!("Could not open {}", args.file.display()))?;
+            let report = datafile::import_delimited(&mut datafile, file, &options, args.dry_run)?;
+            print_import_report(&report, &categories, "Column", args.dry_run);
+        }
+        ImportCommand::Json { file, yes } => {
+            let reader = std::fs::File::open(file)
+                .with_context(|| format!("Could not open {}", file.display()))?;
+            let dump: datafile::DatafileDump =
+                serde_json::from_reader(std::io::BufReader::new(reader))
+                    .with_context(|| format!("Could not parse {}", file.display()))?;
+            let datafile_path = opt.datafile.as_ref().unwrap();
+            if !datafile_path.exists() {
+                datafile::create_new_datafile(datafile_path, &[], None)?;
+            } else if !yes
+                && !confirm(&format!(
+                    "Replace the content of the datafile with {}? The datafile is backed up first.",
+                    file.display()
+                ))?
+            {
+                println!("Aborted");
+                return Ok(());
+            }
+            let mut datafile = open_datafile(opt)?;
+            datafile.restore_dump(&dump)?;
+            println!(
+                "Imported {} categories and {} days from {}",
+                dump.categories.len(),
+                dump.days.len(),
+                file.display()
+            );
+        }
+        ImportCommand::Loop {
+            file,
+            on_conflict,
+            dry_run,
+        } => {
+            if !file.exists() {
+                bail!("{} does not exist", file.display());
+            }
+            let mut datafile = open_datafile_for_import(opt, *dry_run)?;
+            let categories = datafile.get_categories()?;
+            let report =
+                datafile::import_loop_backup(&mut datafile, file, (*on_conflict).into(), *dry_run)?;
+            print_import_report(&report, &categories, "Habit", *dry_run);
+        }
+        ImportCommand::Daylio(args) => {
+            let file = std::fs::File::open(&args.file)
+                .with_context(|| format!("Could not open {}", args.file.display()))?;
+            let log = datafile::read_daylio_export(file)?;
+            import_activities(opt, &log, args, "Activity")?;
+        }
+        ImportCommand::Habitica(args) => {
+            let file = std::fs::File::open(&args.file)
+                .with_context(|| format!("Could not open {}", args.file.display()))?;
+            let log = datafile::read_habitica_export(std::io::BufReader::new(file))?;
+            import_activities(opt, &log, args, "Daily")?;
+        }
+    }
+    Ok(())
+}
+
+/// Imports the activities logged by another app, asking for the categories of the activities
+/// that are not mapped by the arguments. `source` names the activities, e.g. "Daily".
+fn import_activities(
+    opt: &CliOptions,
+    log: &datafile::ActivityLog,
+    args: &ActivityImportArgs,
+    source: &str,
+) -> Result<()> {
+    let mut mapping = vec![];
+    if let Some(ref path) = args.mapping_file {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Could not read {}", path.display()))?;
+        for (index, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let pair = parse_mapping(line)
+                .with_context(|| format!("Line {} of {}", index + 1, path.display()))?;
+            mapping.push(pair);
+        }
+    }
+    // The mappings of the command line take precedence over the ones in the file
+    mapping.splice(0..0, args.mapping.iter().cloned());
+
+    let mut datafile = open_datafile_for_import(opt, args.dry_run)?;
+    let categories = datafile.get_categories()?;
+    let mut resolved = vec![];
+    for activity in &log.activities {
+        let category = match mapping.iter().find(|(mapped, _)| mapped == activity) {
+            Some((_, category)) => category.clone(),
+            None if args.yes => activity.clone(),
+            None => ask_category(activity, log.count_days(activity), &categories)?,
+        };
+        if category != "-" {
+            resolved.push((activity.clone(), category));
+        }
+    }
+
+    let report = datafile::import_activities(
+        &mut datafile,
+        log,
+        &resolved,
+        args.on_conflict.into(),
+        args.dry_run,
+    )?;
+    print_import_report(&report, &categories, source, args.dry_run);
+    Ok(())
+}
+
+/// Asks for the category of an activity. Defaults to the activity name, "-" ignores the activity.
+fn ask_category(activity: &str, days: usize, categories: &[datafile::Category]) -> Result<String> {
+    let exists = categories.iter().any(|category| category.name == activity);
+    print!(
+        "Category of \"{}\" ({} days), empty for {} \"{}\", \"-\" to ignore: ",
+        activity,
+        days,
+        if exists { "existing" } else { "new" },
+        activity
+    );
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(match answer.trim() {
+        "" => activity.to_string(),
+        category => category.to_string(),
+    })
+}
+
+/// Prints the summary of an import. `source` names what is mapped to the categories, e.g. "Column".
+fn print_import_report(
+    report: &datafile::ImportReport,
+    categories: &[datafile::Category],
+    source: &str,
+    dry_run: bool,
+) {
+    if !report.created_categories.is_empty() {
+        let names: Vec<String> = report
+            .created_categories
+            .iter()
+            .map(|category| {
+                if category.hidden {
+                    format!("{} (hidden)", category.name)
+                } else {
+                    category.name.clone()
+                }
+            })
+            .collect();
+        println!(
+            "{}: {}",
+            if dry_run {
+                "Would create categories"
+            } else {
+                "Created categories"
+            },
+            names.join(", ")
+        );
+    }
+    for (column, category_id) in &report.mapped_columns {
+        let name = categories
+            .iter()
+            .chain(&report.created_categories)
+            .find(|category| category.id == *category_id)
+            .map(|category| category.name.as_str())
+            .unwrap_or_default();
+        println!("{} \"{}\" -> category \"{}\"", source, column, name);
+    }
+    if !report.ignored_columns.is_empty() {
+        println!("Ignored: {}", report.ignored_columns.join(", "));
+    }
+    if !report.unrecognized_values.is_empty() {
+        let values: Vec<_> = report.unrecognized_values.iter().cloned().collect();
+        println!("Values not imported as done: {}", values.join(", "));
+    }
+    println!(
+        "{} {} entries: {} new days, {} updated days, {} tracked days skipped",
+        if dry_run { "Would import" } else { "Imported" },
+        report.entries,
+        report.new_days,
+        report.updated_days,
+        report.skipped_days
+    );
+}
+
+fn doctor(opt: &CliOptions, fix: bool) -> Result<()> {
+    // The backup is only made before an actual repair
+    let mut datafile = open_datafile_without_backup(opt)?;
+    let report = datafile.check_integrity()?;
+    print_integrity_report(&report);
+    if report.is_healthy() {
+        println!("No problems found");
+    } else if fix {
+        let backup =
+            datafile.repair_with_backup(opt.datafile.as_ref().unwrap(), &get_backup_policy(opt))?;
+        println!("Backup {} is saved to {}", backup.id, backup.path.display());
+        let report = datafile.check_integrity()?;
+        if !report.is_healthy() {
+            bail!("Some problems could not be repaired");
+        }
+        println!("The problems are repaired");
+    } else {
+        println!("Run with --fix to repair the problems");
+    }
+    Ok(())
+}
+
+fn print_integrity_report(report: &datafile::IntegrityReport) {
+    println!("Schema version: {}", report.version);
+    match report.date_range {
+        Some((from, until)) => println!("Date range: {} - {}", from, until),
+        None => println!("Date range: no tracked days"),
+    }
+    println!("Categories:");
+    for category in &report.categories {
+        let hidden = if category.hidden { " (hidden)" } else { "" };
+        println!("- {} [{}]{}", category.name, category.id, hidden);
+    }
+    if report.orphan_entries > 0 {
+        println!(
+            "Entries referring to a missing day or category: {}",
+            report.orphan_entries
+        );
+    }
+    if report.orphan_excused_ranges > 0 {
+        println!(
+            "Excused ranges referring to a missing category: {}",
+            report.orphan_excused_ranges
+        );
+    }
+    if !report.duplicate_names.is_empty() {
+        println!(
+            "Duplicate category names: {}",
+            report.duplicate_names.join(", ")
+        );
+    }
+    if report.invalid_dates > 0 {
+        println!("Invalid dates: {}", report.invalid_dates);
+    }
+}
+
 fn handle_config() -> Result<CliOptions> {
     let opt = CliOptions::parse();
     let persistent_config = configuration::load_config()?;
@@ -99,11 +1204,23 @@ fn merge_cli_and_persistent_options(
         list_most_frequent_days: options_from_cli
             .list_most_frequent_days
             .or(Some(persistent_config.list_most_frequent_days)),
+        period_alignment: options_from_cli
+            .period_alignment
+            .or(Some(persistent_config.period_alignment)),
+        week_start: options_from_cli
+            .week_start
+            .or(Some(persistent_config.week_start)),
+        backup_dir: options_from_cli
+            .backup_dir
+            .or_else(|| persistent_config.backup_dir.clone()),
+        backup_count: options_from_cli
+            .backup_count
+            .or(Some(persistent_config.backup_count)),
         ..options_from_cli
     }
 }
 
-fn create_new(path: &Path, headers_string: &str) -> Result<()> {
+fn create_new(path: &Path, headers_string: &str, encrypt: bool) -> Result<()> {
     let mut headers_vector = vec![];
     for title in headers_string.split(',') {
         if title.is_empty() {
@@ -111,13 +1228,21 @@ fn create_new(path: &Path, headers_string: &str) -> Result<()> {
         }
         headers_vector.push(String::from(title));
     }
-    datafile::create_new_datafile(path, &headers_vector)?;
+    let key = if encrypt {
+        match read_new_passphrase()? {
+            Some(key) => Some(key),
+            None => bail!("The passphrase must not be empty"),
+        }
+    } else {
+        None
+    };
+    datafile::create_new_datafile(path, &headers_vector, key.as_deref())?;
     println!("New datafile successfully created at {}", path.display());
     Ok(())
 }
 
-fn add_category(datafile_path: &Path, name: &str) -> Result<()> {
-    let datafile = datafile::open_datafile(datafile_path)?;
+fn add_category(opt: &CliOptions, name: &str) -> Result<()> {
+    let datafile = open_datafile(opt)?;
     match datafile.add_category(name)? {
         datafile::AddCategoryResult::AddedNew => {
             println!("Added new category \"{}\"", name);
@@ -135,8 +1260,8 @@ fn add_category(datafile_path: &Path, name: &str) -> Result<()> {
     Ok(())
 }
 
-fn hide_category(datafile_path: &Path, name: &str) -> Result<()> {
-    let datafile = datafile::open_datafile(datafile_path)?;
+fn hide_category(opt: &CliOptions, name: &str) -> Result<()> {
+    let datafile = open_datafile(opt)?;
     match datafile.hide_category(name)? {
         datafile::HideCategoryResult::AlreadyHidden => {
             bail!("Category \"{}\" was already hidden", name)
@@ -150,3 +1275,331 @@ fn hide_category(datafile_path: &Path, name: &str) -> Result<()> {
     }
     Ok(())
 }
+
+fn set_note(opt: &CliOptions, text: &str, date: Option<NaiveDate>) -> Result<()> {
+    let mut datafile = open_datafile(opt)?;
+    let date = date.unwrap_or_else(|| Local::now().date_naive());
+    let text = text.trim();
+    datafile.set_note(&date, text)?;
+    if text.is_empty() {
+        println!("Removed the note from {}", date);
+    } else {
+        println!("Note is set on {}", date);
+    }
+    Ok(())
+}
+
+fn handle_category_command(opt: &CliOptions, command: &CategoryCommand) -> Result<()> {
+    // Asked before opening the datafile, so that declining does not make a backup
+    if let CategoryCommand::Delete { name, yes: false } = command
+        && !confirm(&format!(
+            "Delete category \"{}\" together with all of its entries?",
+            name
+        ))?
+    {
+        println!("Nothing was deleted");
+        return Ok(());
+    }
+    let mut datafile = open_datafile(opt)?;
+    match command {
+        CategoryCommand::List => {
+            for category in datafile.get_categories()? {
+                let mut line = category.name;
+                if let Some(symbol) = category.details.symbol {
+                    line = format!("{} {}", symbol, line);
+                }
+                if let Some(display_name) = category.details.display_name {
+                    line = format!("{} - {}", line, display_name);
+                }
+                if let Some(description) = category.details.description {
+                    line = format!("{}: {}", line, description);
+                }
+                if category.polarity == datafile::Polarity::Avoid {
+                    line.push_str(" (avoid)");
+                }
+                if category.schedule != datafile::Schedule::default() {
+                    line = format!("{} ({})", line, category.schedule);
+                }
+                if category.hidden {
+                    line.push_str(" (hidden)");
+                }
+                println!("{}", line);
+            }
+        }
+        CategoryCommand::Rename { name, new_name } => {
+            match datafile.rename_category(name, new_name)? {
+                datafile::RenameCategoryResult::Renamed => {
+                    println!("Category \"{}\" is renamed to \"{}\"", name, new_name)
+                }
+                datafile::RenameCategoryResult::NameTaken => {
+                    bail!("Category \"{}\" already exists", new_name)
+                }
+                datafile::RenameCategoryResult::InvalidName => {
+                    bail!("Invalid category name: \"{}\"", new_name)
+                }
+                datafile::RenameCategoryResult::NonExistingCategory => {
+                    bail!("Category \"{}\" does not exist", name)
+                }
+            }
+        }
+        CategoryCommand::Edit {
+            name,
+            display_name,
+            description,
+            symbol,
+            color,
+            polarity,
+        } => {
+            let Some(category) = datafile
+                .get_categories()?
+                .into_iter()
+                .find(|category| &category.name == name)
+            else {
+                bail!("Category \"{}\" does not exist", name)
+            };
+            if let Some(color) = color
+                && !color.is_empty()
+                && ratatui::style::Color::from_str(color).is_err()
+            {
+                bail!("Invalid colour: {}", color);
+            }
+            let update = |new_value: &Option<String>, old_value: Option<String>| match new_value {
+                Some(value) if value.is_empty() => None,
+                Some(value) => Some(value.clone()),
+                None => old_value,
+            };
+            let details = datafile::CategoryDetails {
+                display_name: update(display_name, category.details.display_name),
+                description: update(description, category.details.description),
+                symbol: update(symbol, category.details.symbol),
+                color: update(color, category.details.color),
+            };
+            datafile.set_category_details(name, &details)?;
+            if let Some(polarity) = polarity {
+                datafile.set_category_polarity(name, (*polarity).into())?;
+            }
+            println!("Category \"{}\" is updated", name);
+        }
+        CategoryCommand::Schedule {
+            name,
+            schedule,
+            from,
+        } => {
+            let schedule = parse_schedule(schedule, from)?;
+            match datafile.set_category_schedule(name, &schedule)? {
+                datafile::UpdateCategoryResult::Updated => {
+                    println!("Category \"{}\" is scheduled {}", name, schedule)
+                }
+                datafile::UpdateCategoryResult::NonExistingCategory => {
+                    bail!("Category \"{}\" does not exist", name)
+                }
+            }
+        }
+        CategoryCommand::Reorder { category_list } => {
+            let names: Vec<String> = category_list.split(',').map(String::from).collect();
+            match datafile.reorder_categories(&names)? {
+                datafile::UpdateCategoryResult::Updated => println!("Categories are reordered"),
+                datafile::UpdateCategoryResult::NonExistingCategory => {
+                    bail!("Invalid category list: {}", category_list)
+                }
+            }
+        }
+        CategoryCommand::Merge { source, target } => {
+            match datafile.merge_category(source, target)? {
+                datafile::MergeCategoryResult::Merged => {
+                    println!("Category \"{}\" is merged into \"{}\"", source, target)
+                }
+                datafile::MergeCategoryResult::SameCategory => {
+                    bail!("Cannot merge category \"{}\" into itself", source)
+                }
+                datafile::MergeCategoryResult::NonExistingSource => {
+                    bail!("Category \"{}\" does not exist", source)
+                }
+                datafile::MergeCategoryResult::NonExistingTarget => {
+                    bail!("Category \"{}\" does not exist", target)
+                }
+            }
+        }
+        CategoryCommand::Delete { name, .. } => match datafile.delete_category(name)? {
+            datafile::UpdateCategoryResult::Updated => {
+                println!("Category \"{}\" is deleted", name)
+            }
+            datafile::UpdateCategoryResult::NonExistingCategory => {
+                bail!("Category \"{}\" does not exist", name)
+            }
+        },
+    }
+    Ok(())
+}
+
+/// Parses a column mapping like "Gym=GYM".
+fn parse_mapping(spec: &str) -> Result<(String, String)> {
+    match spec.split_once('=') {
+        Some((column, category)) if !column.is_empty() && !category.is_empty() => {
+            Ok((String::from(column), String::from(category)))
+        }
+        _ => bail!("Invalid mapping, expected NAME=CATEGORY: {}", spec),
+    }
+}
+
+fn parse_schedule(spec: &str, from: &Option<NaiveDate>) -> Result<datafile::Schedule> {
+    if spec == "daily" {
+        return Ok(datafile::Schedule::default());
+    }
+    if let Some(days) = spec.strip_prefix("every:") {
+        let days: usize = days.parse().context("Invalid number of days")?;
+        if days == 0 {
+            bail!("Number of days must be positive");
+        }
+        return Ok(datafile::Schedule::EveryNDays {
+            days,
+            anchor: from.unwrap_or_else(|| Local::now().date_naive()),
+        });
+    }
+    let mut weekdays = chrono::WeekdaySet::EMPTY;
+    for name in spec.split(',') {
+        let Ok(weekday) = chrono::Weekday::from_str(name.trim()) else {
+            bail!("Invalid weekday: {}", name);
+        };
+        weekdays.insert(weekday);
+    }
+    Ok(datafile::Schedule::Weekdays(weekdays))
+}
+
+fn confirm(question: &str) -> Result<bool> {
+    print!("{} [y/N] ", question);
+    std::io::stdout().flush()?;
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+fn handle_target_command(opt: &CliOptions, command: &TargetCommand) -> Result<()> {
+    let datafile = open_datafile(opt)?;
+    match command {
+        TargetCommand::Set {
+            name,
+            at_least,
+            at_most,
+            days,
+        } => {
+            if *days == 0 {
+                bail!("The period of the target must be at least 1 day long");
+            }
+            let target = match (at_least, at_most) {
+                (Some(count), _) => datafile::Target {
+                    kind: datafile::TargetKind::AtLeast,
+                    count: *count,
+                    days: *days,
+                },
+                (None, Some(count)) => datafile::Target {
+                    kind: datafile::TargetKind::AtMost,
+                    count: *count,
+                    days: *days,
+                },
+                (None, None) => bail!("Either --at-least or --at-most must be specified"),
+            };
+            match datafile.set_target(name, Some(target))? {
+                datafile::UpdateCategoryResult::Updated => {
+                    println!("Target of category \"{}\" is set to {}", name, target)
+                }
+                datafile::UpdateCategoryResult::NonExistingCategory => {
+                    bail!("Category \"{}\" does not exist", name)
+                }
+            }
+        }
+        TargetCommand::Clear { name } => match datafile.set_target(name, None)? {
+            datafile::UpdateCategoryResult::Updated => {
+                println!("Target of category \"{}\" is removed", name)
+            }
+            datafile::UpdateCategoryResult::NonExistingCategory => {
+                bail!("Category \"{}\" does not exist", name)
+            }
+        },
+        TargetCommand::Check => check_targets(&datafile)?,
+    }
+    Ok(())
+}
+
+fn handle_excuse_command(opt: &CliOptions, command: &ExcuseCommand) -> Result<()> {
+    let mut datafile = open_datafile(opt)?;
+    let categories = datafile.get_categories()?;
+    match command {
+        ExcuseCommand::Add(args) | ExcuseCommand::Remove(args) => {
+            let excused = matches!(command, ExcuseCommand::Add(_));
+            let until = args.until.unwrap_or(args.from);
+            if until < args.from {
+                bail!("The last day of the range is earlier than the first day");
+            }
+            let category_id = match args.category {
+                Some(ref name) => match categories.iter().find(|category| &category.name == name) {
+                    Some(category) => Some(category.id),
+                    None => bail!("Category \"{}\" does not exist", name),
+                },
+                None => None,
+            };
+            datafile.set_excused(category_id, &args.from, &until, excused)?;
+            let scope = match args.category {
+                Some(ref name) => format!("Category \"{}\"", name),
+                None => String::from("The diary"),
+            };
+            println!(
+                "{} is {} from {} until {}",
+                scope,
+                if excused {
+                    "excused"
+                } else {
+                    "no longer excused"
+                },
+                args.from,
+                until
+            );
+        }
+        ExcuseCommand::List => {
+            let ranges = datafile.get_excused_ranges()?;
+            if ranges.is_empty() {
+                println!("No days are excused");
+            }
+            for range in ranges {
+                let scope = match range.category_id {
+                    Some(id) => categories
+                        .iter()
+                        .find(|category| category.id == id)
+                        .map(|category| category.name.clone())
+                        .unwrap_or_default(),
+                    None => String::from("all categories"),
+                };
+                println!("{} - {}: {}", range.from, range.until, scope);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn check_targets(datafile: &datafile::DiaryDataSqlite) -> Result<()> {
+    let targets = datafile.get_targets()?;
+    let today = Local::now().date_naive();
+    let mut any_target = false;
+    for (idx, category) in datafile.get_header()?.iter().enumerate() {
+        let Some(target) = targets.get(&category.id) else {
+            continue;
+        };
+        any_target = true;
+        let period = datafile::get_date_ranges(&today, target.days, 1);
+        let stats = datafile.calculate_data_counts_per_iter(&period)?[0][idx];
+        let count = stats.weighted_count();
+        let status = match target.kind {
+            _ if target.is_met(count, &category.schedule, stats.scheduled_days) => "on track",
+            datafile::TargetKind::AtLeast => "behind",
+            datafile::TargetKind::AtMost => "over the limit",
+        };
+        println!(
+            "{}: {} times in the last {} days, target is {}: {}",
+            category.name, count, target.days, target, status
+        );
+    }
+    if !any_target {
+        println!("No targets are set");
+    }
+    Ok(())
+}
@@ -1,3 +1,4 @@
+mod day_note_widget;
 mod habit_day_list_widget;
 mod habit_frequency_table_widget;
 mod table_utils;
@@ -12,10 +13,11 @@ use crossterm::{
     ExecutableCommand,
     event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
 };
-use genee::datafile::{self, DiaryDataSqlite};
+use genee::datafile::{CalendarPeriod, DiaryDataSqlite, DiaryStore};
 use ratatui::prelude::*;
 
 use self::{
+    day_note_widget::{DayNoteWidget, DayNoteWidgetInput},
     habit_day_list_widget::{HabitDayListWidget, HabitDayListWidgetInput},
     habit_frequency_table_widget::{HabitFrequencyTableWidget, HabitFrequencyTableWidgetInput},
     top_occurrence_list_widget::{TopOccurrenceListWidget, TopOccurrenceListWidgetInput},
@@ -44,6 +46,7 @@ pub fn run_app(opts: &CliOptions) -> Result<()> {
 struct UiApp {
     datafile: DiaryDataSqlite,
     habit_day_list_widget: HabitDayListWidget,
+    day_note_widget: DayNoteWidget,
     habit_frequency_table_widget: HabitFrequencyTableWidget,
     top_occurrence_list_widget: TopOccurrenceListWidget,
     opts: CliOptions,
@@ -88,6 +91,16 @@ impl Scale {
             Scale::FiveYearly => 1825,
         }
     }
+
+    fn calendar_period(&self) -> CalendarPeriod {
+        match self {
+            Scale::Weekly => CalendarPeriod::Week,
+            Scale::Monthly => CalendarPeriod::Month,
+            Scale::Quarterly => CalendarPeriod::Quarter,
+            Scale::Yearly => CalendarPeriod::Year,
+            Scale::FiveYearly => CalendarPeriod::FiveYears,
+        }
+    }
 }
 
 impl Display for Scale {
@@ -104,9 +117,10 @@ impl Display for Scale {
 
 impl UiApp {
     fn new(opts: &CliOptions) -> Result<Self> {
-        let datafile = datafile::open_datafile(opts.datafile.as_ref().unwrap())?;
+        let datafile = crate::open_datafile(opts)?;
         let start_date = Local::now().date_naive();
         let habit_day_list_widget = HabitDayListWidget::new(&datafile, start_date)?;
+        let day_note_widget = DayNoteWidget::new(&datafile, start_date)?;
         let habit_frequency_table_widget = HabitFrequencyTableWidget::new(
             &datafile,
             start_date,
@@ -119,6 +133,7 @@ impl UiApp {
         Ok(UiApp {
             datafile,
             habit_day_list_widget,
+            day_note_widget,
             habit_frequency_table_widget,
             top_occurrence_list_widget,
             opts: opts.clone(),
@@ -132,25 +147,49 @@ impl UiApp {
                 if key.kind != KeyEventKind::Press {
                     return Ok(false);
                 }
+                if self.habit_day_list_widget.is_editing_value() {
+                    self.handle_value_edit_key(key.code)?;
+                    return Ok(false);
+                }
+                if self.day_note_widget.is_editing() {
+                    self.handle_note_edit_key(key.code)?;
+                    return Ok(false);
+                }
                 if key.code == KeyCode::Char('q') {
                     return Ok(true);
                 }
+                let editing_key = matches!(key.code, KeyCode::Char(' ' | 'x' | 'X' | 'e' | 'n'));
+                if editing_key && self.datafile.is_read_only() {
+                    return Ok(false);
+                }
                 if key.code == KeyCode::Up && key.modifiers == KeyModifiers::NONE {
                     self.habit_day_list_widget
                         .update(&mut self.datafile, HabitDayListWidgetInput::StepEarlier)?;
-                    self.update_frequency_table()?;
+                    self.update_selected_date()?;
+                } else if key.code == KeyCode::Up && key.modifiers == KeyModifiers::SHIFT {
+                    self.habit_day_list_widget.update(
+                        &mut self.datafile,
+                        HabitDayListWidgetInput::ExtendRangeEarlier,
+                    )?;
+                    self.update_selected_date()?;
+                } else if key.code == KeyCode::Down && key.modifiers == KeyModifiers::SHIFT {
+                    self.habit_day_list_widget.update(
+                        &mut self.datafile,
+                        HabitDayListWidgetInput::ExtendRangeLater,
+                    )?;
+                    self.update_selected_date()?;
                 } else if key.code == KeyCode::PageUp {
                     self.habit_day_list_widget
                         .update(&mut self.datafile, HabitDayListWidgetInput::StrideEarlier)?;
-                    self.update_frequency_table()?;
+                    self.update_selected_date()?;
                 } else if key.code == KeyCode::Down && key.modifiers == KeyModifiers::NONE {
                     self.habit_day_list_widget
                         .update(&mut self.datafile, HabitDayListWidgetInput::StepLater)?;
-                    self.update_frequency_table()?;
+                    self.update_selected_date()?;
                 } else if key.code == KeyCode::PageDown {
                     self.habit_day_list_widget
                         .update(&mut self.datafile, HabitDayListWidgetInput::StrideLater)?;
-                    self.update_frequency_table()?;
+                    self.update_selected_date()?;
                 } else if key.code == KeyCode::Left && key.modifiers == KeyModifiers::NONE {
                     self.habit_day_list_widget.update(
                         &mut self.datafile,
@@ -167,6 +206,33 @@ impl UiApp {
                     self.habit_frequency_table_widget
                         .update(&self.datafile, HabitFrequencyTableWidgetInput::DataChanged)?;
                     self.update_top_occurrence_table()?;
+                } else if key.code == KeyCode::Char('x') || key.code == KeyCode::Char('X') {
+                    let input = if key.code == KeyCode::Char('x') {
+                        HabitDayListWidgetInput::SwitchExcused
+                    } else {
+                        HabitDayListWidgetInput::SwitchCategoryExcused
+                    };
+                    self.habit_day_list_widget
+                        .update(&mut self.datafile, input)?;
+                    self.habit_frequency_table_widget
+                        .update(&self.datafile, HabitFrequencyTableWidgetInput::DataChanged)?;
+                } else if key.code == KeyCode::Char('e') {
+                    self.habit_day_list_widget
+                        .update(&mut self.datafile, HabitDayListWidgetInput::BeginValueEdit)?;
+                } else if key.code == KeyCode::Char('n') {
+                    self.day_note_widget
+                        .update(&mut self.datafile, DayNoteWidgetInput::BeginEdit)?;
+                } else if key.code == KeyCode::Char('v') {
+                    self.habit_frequency_table_widget.update(
+                        &self.datafile,
+                        HabitFrequencyTableWidgetInput::NextStatistic,
+                    )?;
+                } else if key.code == KeyCode::Char('c') {
+                    self.habit_frequency_table_widget.update(
+                        &self.datafile,
+                        HabitFrequencyTableWidgetInput::SwitchAlignment,
+                    )?;
+                    self.update_top_occurrence_table()?;
                 } else if key.code == KeyCode::Left && key.modifiers == KeyModifiers::CONTROL {
                     self.habit_frequency_table_widget
                         .update(&self.datafile, HabitFrequencyTableWidgetInput::SmallerScale)?;
@@ -189,6 +255,37 @@ impl UiApp {
         Ok(false)
     }
 
+    fn handle_value_edit_key(&mut self, code: KeyCode) -> Result<()> {
+        let input = match code {
+            KeyCode::Char(c) => HabitDayListWidgetInput::EditValue(c),
+            KeyCode::Backspace => HabitDayListWidgetInput::EditValueBackspace,
+            KeyCode::Enter => HabitDayListWidgetInput::ConfirmValueEdit,
+            KeyCode::Esc => HabitDayListWidgetInput::CancelValueEdit,
+            _ => return Ok(()),
+        };
+        let confirmed = matches!(input, HabitDayListWidgetInput::ConfirmValueEdit);
+        self.habit_day_list_widget
+            .update(&mut self.datafile, input)?;
+        if confirmed {
+            self.habit_frequency_table_widget
+                .update(&self.datafile, HabitFrequencyTableWidgetInput::DataChanged)?;
+            self.update_top_occurrence_table()?;
+        }
+        Ok(())
+    }
+
+    fn handle_note_edit_key(&mut self, code: KeyCode) -> Result<()> {
+        let input = match code {
+            KeyCode::Char(c) => DayNoteWidgetInput::EditNote(c),
+            KeyCode::Backspace => DayNoteWidgetInput::EditNoteBackspace,
+            KeyCode::Enter => DayNoteWidgetInput::ConfirmEdit,
+            KeyCode::Esc => DayNoteWidgetInput::CancelEdit,
+            _ => return Ok(()),
+        };
+        self.day_note_widget.update(&mut self.datafile, input)?;
+        Ok(())
+    }
+
     fn render(&mut self, frame: &mut Frame) {
         let horizontal_chunks = Layout::default()
             .direction(Direction::Horizontal)
@@ -201,14 +298,29 @@ impl UiApp {
                 Constraint::Max(self.top_occurrence_list_widget.expected_height() as u16),
             ])
             .split(horizontal_chunks[1]);
+        let day_vertical_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(5)])
+            .split(horizontal_chunks[0]);
         self.habit_day_list_widget
-            .render(frame, horizontal_chunks[0]);
+            .render(frame, day_vertical_chunks[0]);
+        self.day_note_widget.render(frame, day_vertical_chunks[1]);
         self.habit_frequency_table_widget
             .render(frame, left_vertical_chunks[0]);
         self.top_occurrence_list_widget
             .render(frame, left_vertical_chunks[1]);
     }
 
+    fn update_selected_date(&mut self) -> Result<()> {
+        if let Some(selected_date) = self.habit_day_list_widget.get_selected_date() {
+            self.day_note_widget.update(
+                &mut self.datafile,
+                DayNoteWidgetInput::SetDate(selected_date),
+            )?;
+        }
+        self.update_frequency_table()
+    }
+
     fn update_frequency_table(&mut self) -> Result<()> {
         let selected_date = self
             .habit_day_list_widget
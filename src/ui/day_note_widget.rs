@@ -0,0 +1,103 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use genee::datafile::DiaryStore;
+use ratatui::{prelude::*, widgets::*};
+
+pub struct DayNoteWidget {
+    date: NaiveDate,
+    note: Option<String>,
+    note_input: Option<String>,
+    read_only: bool,
+}
+
+pub enum DayNoteWidgetInput {
+    SetDate(NaiveDate),
+    BeginEdit,
+    EditNote(char),
+    EditNoteBackspace,
+    ConfirmEdit,
+    CancelEdit,
+}
+
+impl DayNoteWidget {
+    pub fn new(datafile: &impl DiaryStore, date: NaiveDate) -> Result<Self> {
+        Ok(DayNoteWidget {
+            date,
+            note: datafile.get_note(&date)?,
+            note_input: None,
+            read_only: datafile.is_read_only(),
+        })
+    }
+
+    pub fn update(
+        &mut self,
+        datafile: &mut impl DiaryStore,
+        input: DayNoteWidgetInput,
+    ) -> Result<()> {
+        match input {
+            DayNoteWidgetInput::SetDate(date) => {
+                if date != self.date {
+                    self.date = date;
+                    self.note = datafile.get_note(&date)?;
+                }
+            }
+            DayNoteWidgetInput::BeginEdit => {
+                self.note_input = Some(self.note.clone().unwrap_or_default());
+            }
+            DayNoteWidgetInput::EditNote(c) => {
+                if let Some(ref mut input) = self.note_input {
+                    input.push(c);
+                }
+            }
+            DayNoteWidgetInput::EditNoteBackspace => {
+                if let Some(ref mut input) = self.note_input {
+                    input.pop();
+                }
+            }
+            DayNoteWidgetInput::ConfirmEdit => {
+                if let Some(input) = self.note_input.take() {
+                    let input = input.trim();
+                    datafile.set_note(&self.date, input)?;
+                    self.note = if input.is_empty() {
+                        None
+                    } else {
+                        Some(String::from(input))
+                    };
+                }
+            }
+            DayNoteWidgetInput::CancelEdit => {
+                self.note_input = None;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_editing(&self) -> bool {
+        self.note_input.is_some()
+    }
+
+    pub fn render(&self, frame: &mut Frame, area: Rect) {
+        let paragraph = if let Some(ref input) = self.note_input {
+            Paragraph::new(format!("{}_", input)).style(Style::default().fg(Color::LightYellow))
+        } else if let Some(ref note) = self.note {
+            Paragraph::new(note.as_str())
+        } else {
+            Paragraph::new("No note").style(Style::default().fg(Color::DarkGray))
+        };
+        let footer = if self.is_editing() {
+            "Confirm: <ENTER> Cancel: <ESC>"
+        } else if self.read_only {
+            ""
+        } else {
+            "Edit note: <n>"
+        };
+        frame.render_widget(
+            paragraph.wrap(Wrap { trim: true }).block(
+                Block::bordered()
+                    .title_top(format!("Note on {}", self.date))
+                    .title_bottom(footer),
+            ),
+            area,
+        );
+    }
+}
@@ -1,18 +1,34 @@
 use super::{Scale, table_utils};
 use anyhow::Result;
 use chrono::NaiveDate;
-use genee::datafile::DiaryDataSqlite;
+use genee::datafile::{Category, CategoryId, DiaryStore, EntryStatus, ExcusedRange, Polarity};
 use ratatui::{prelude::*, widgets::*};
 
 const DEFAULT_STARTING_HABIT_ROWS: usize = 100;
 
+#[derive(Clone, Copy, Default)]
+struct HabitCell {
+    status: Option<EntryStatus>,
+    value: Option<f64>,
+}
+
+struct HabitRow {
+    date: NaiveDate,
+    cells: Option<Vec<HabitCell>>,
+    excused: Vec<bool>,
+}
+
 pub struct HabitDayListWidget {
-    header: Vec<(String, usize)>,
+    header: Vec<Category>,
     habit_table_state: TableState,
-    habit_rows: Vec<(NaiveDate, Option<Vec<bool>>)>,
+    habit_rows: Vec<HabitRow>,
+    range_anchor: Option<usize>,
     start_date: NaiveDate,
     edit_col_idx: usize,
     scale: Scale,
+    value_input: Option<String>,
+    streak_info: String,
+    read_only: bool,
 }
 
 pub enum HabitDayListWidgetInput {
@@ -20,12 +36,21 @@ pub enum HabitDayListWidgetInput {
     StepLater,
     StrideEarlier,
     StrideLater,
+    ExtendRangeEarlier,
+    ExtendRangeLater,
     NavigateColumn(isize),
     SwitchValue,
+    BeginValueEdit,
+    EditValue(char),
+    EditValueBackspace,
+    ConfirmValueEdit,
+    CancelValueEdit,
+    SwitchExcused,
+    SwitchCategoryExcused,
 }
 
 impl HabitDayListWidget {
-    pub fn new(datafile: &DiaryDataSqlite, start_date: NaiveDate) -> Result<Self> {
+    pub fn new(datafile: &impl DiaryStore, start_date: NaiveDate) -> Result<Self> {
         let mut habit_table_state = TableState::default();
         habit_table_state.select(Some(0));
 
@@ -33,32 +58,49 @@ impl HabitDayListWidget {
             header: datafile.get_header()?,
             habit_table_state,
             habit_rows: vec![],
+            range_anchor: None,
             start_date,
             edit_col_idx: 0,
             scale: Scale::Monthly,
+            value_input: None,
+            streak_info: String::new(),
+            read_only: datafile.is_read_only(),
         };
         widget.load_habit_row_batch(datafile, &start_date)?;
+        widget.update_streak_info(datafile)?;
         Ok(widget)
     }
 
     pub fn update(
         &mut self,
-        datafile: &mut DiaryDataSqlite,
+        datafile: &mut impl DiaryStore,
         input: HabitDayListWidgetInput,
     ) -> Result<()> {
         match input {
             HabitDayListWidgetInput::StepEarlier => {
+                self.range_anchor = None;
                 self.navigate_date(datafile, 1)?;
             }
             HabitDayListWidgetInput::StepLater => {
+                self.range_anchor = None;
                 self.navigate_date(datafile, -1)?;
             }
             HabitDayListWidgetInput::StrideEarlier => {
+                self.range_anchor = None;
                 self.navigate_date(datafile, self.scale.value() as isize)?;
             }
             HabitDayListWidgetInput::StrideLater => {
+                self.range_anchor = None;
                 self.navigate_date(datafile, -(self.scale.value() as isize))?;
             }
+            HabitDayListWidgetInput::ExtendRangeEarlier => {
+                self.range_anchor = self.range_anchor.or(self.habit_table_state.selected());
+                self.navigate_date(datafile, 1)?;
+            }
+            HabitDayListWidgetInput::ExtendRangeLater => {
+                self.range_anchor = self.range_anchor.or(self.habit_table_state.selected());
+                self.navigate_date(datafile, -1)?;
+            }
             HabitDayListWidgetInput::NavigateColumn(offset) => {
                 let new_val =
                     ((self.edit_col_idx as isize) + offset).rem_euclid(self.header.len() as isize);
@@ -66,32 +108,148 @@ impl HabitDayListWidget {
             }
             HabitDayListWidgetInput::SwitchValue => {
                 let row_index = self.habit_table_state.selected().unwrap_or_default();
-                let date = self.habit_rows[row_index].0;
-                if self.habit_rows[row_index].1.is_none() {
-                    self.habit_rows[row_index].1 = Some(vec![false; self.header.len()]);
+                let date = self.habit_rows[row_index].date;
+                if self.habit_rows[row_index].cells.is_none() {
+                    self.habit_rows[row_index].cells =
+                        Some(vec![HabitCell::default(); self.header.len()]);
                 }
-                if let Some(ref mut vec) = self.habit_rows[row_index].1 {
+                if let Some(ref mut vec) = self.habit_rows[row_index].cells {
                     let entry = &mut vec[self.edit_col_idx];
-                    *entry = !*entry;
-                    datafile
-                        .update_data(&date, &table_utils::encode_habit_vector(&self.header, vec))?;
+                    entry.status = match entry.status {
+                        None => Some(EntryStatus::Done),
+                        Some(EntryStatus::Done) => Some(EntryStatus::Partial),
+                        Some(EntryStatus::Partial) => Some(EntryStatus::Skipped),
+                        Some(EntryStatus::Skipped) => None,
+                    };
+                    if entry.status.is_none() {
+                        entry.value = None;
+                    }
+                    datafile.set_status(&date, self.header[self.edit_col_idx].id, entry.status)?;
+                }
+            }
+            HabitDayListWidgetInput::BeginValueEdit => {
+                let current_value = self.get_selected_cell().and_then(|cell| cell.value);
+                self.value_input = Some(current_value.map(format_value).unwrap_or_default());
+            }
+            HabitDayListWidgetInput::EditValue(c) => {
+                if let Some(ref mut input) = self.value_input
+                    && (c.is_ascii_digit() || (c == '.' && !input.contains('.')))
+                {
+                    input.push(c);
+                }
+            }
+            HabitDayListWidgetInput::EditValueBackspace => {
+                if let Some(ref mut input) = self.value_input {
+                    input.pop();
+                }
+            }
+            HabitDayListWidgetInput::ConfirmValueEdit => {
+                // An invalid value keeps the input open for correction
+                if let Some(value) = self.value_input.as_deref().and_then(parse_value) {
+                    self.value_input = None;
+                    self.set_selected_value(datafile, value)?;
+                }
+            }
+            HabitDayListWidgetInput::CancelValueEdit => {
+                self.value_input = None;
+            }
+            HabitDayListWidgetInput::SwitchExcused => {
+                self.switch_excused(datafile, None)?;
+            }
+            HabitDayListWidgetInput::SwitchCategoryExcused => {
+                if let Some(category) = self.header.get(self.edit_col_idx) {
+                    self.switch_excused(datafile, Some(category.id))?;
                 }
             }
         }
+        self.update_streak_info(datafile)?;
         Ok(())
     }
 
+    pub fn is_editing_value(&self) -> bool {
+        self.value_input.is_some()
+    }
+
     pub fn get_selected_date(&self) -> Option<NaiveDate> {
         self.habit_table_state
             .selected()
-            .map(|idx| self.habit_rows[idx].0)
+            .map(|idx| self.habit_rows[idx].date)
     }
 
     pub fn get_scale(&self) -> Scale {
         self.scale
     }
 
-    fn navigate_date(&mut self, datafile: &DiaryDataSqlite, offset: isize) -> Result<()> {
+    fn get_selected_cell(&self) -> Option<HabitCell> {
+        let row_index = self.habit_table_state.selected()?;
+        self.habit_rows[row_index]
+            .cells
+            .as_ref()
+            .map(|cells| cells[self.edit_col_idx])
+    }
+
+    fn set_selected_value(
+        &mut self,
+        datafile: &mut impl DiaryStore,
+        value: Option<f64>,
+    ) -> Result<()> {
+        let row_index = self.habit_table_state.selected().unwrap_or_default();
+        let date = self.habit_rows[row_index].date;
+        let cells = self.habit_rows[row_index]
+            .cells
+            .get_or_insert_with(|| vec![HabitCell::default(); self.header.len()]);
+        let cell = &mut cells[self.edit_col_idx];
+        if value.is_none() && cell.status.is_none() {
+            return Ok(());
+        }
+        cell.status.get_or_insert(EntryStatus::Done);
+        cell.value = value;
+        datafile.set_value(&date, self.header[self.edit_col_idx].id, value)?;
+        Ok(())
+    }
+
+    /// Returns the indices of the first and the last selected rows.
+    fn get_selected_row_range(&self) -> Option<(usize, usize)> {
+        let selected = self.habit_table_state.selected()?;
+        let anchor = self.range_anchor.unwrap_or(selected);
+        Some((selected.min(anchor), selected.max(anchor)))
+    }
+
+    /// Excuses the selected days, or removes the excuse if all of them are already excused.
+    fn switch_excused(
+        &mut self,
+        datafile: &mut impl DiaryStore,
+        category_id: Option<CategoryId>,
+    ) -> Result<()> {
+        let Some((first_idx, last_idx)) = self.get_selected_row_range() else {
+            return Ok(());
+        };
+        // Rows are ordered from the latest to the earliest date
+        let from = self.habit_rows[last_idx].date;
+        let until = self.habit_rows[first_idx].date;
+        let excused_ranges = datafile.get_excused_ranges()?;
+        let all_excused = from
+            .iter_days()
+            .take_while(|date| *date <= until)
+            .all(|date| {
+                excused_ranges
+                    .iter()
+                    .any(|range| range.category_id == category_id && range.contains(&date))
+            });
+        datafile.set_excused(category_id, &from, &until, !all_excused)?;
+        self.range_anchor = None;
+        self.refresh_excused(datafile)
+    }
+
+    fn refresh_excused(&mut self, datafile: &impl DiaryStore) -> Result<()> {
+        let excused_ranges = datafile.get_excused_ranges()?;
+        for row in self.habit_rows.iter_mut() {
+            row.excused = get_excused_vector(&self.header, &excused_ranges, &row.date);
+        }
+        Ok(())
+    }
+
+    fn navigate_date(&mut self, datafile: &impl DiaryStore, offset: isize) -> Result<()> {
         assert_ne!(offset, 0);
         let current_row_idx = self.habit_table_state.selected().unwrap_or_default() as isize;
         let new_row_idx = (current_row_idx - offset).max(0isize) as usize;
@@ -102,25 +260,43 @@ impl HabitDayListWidget {
 
     fn load_habit_row_batch(
         &mut self,
-        datafile: &DiaryDataSqlite,
+        datafile: &impl DiaryStore,
         batch_start_date: &NaiveDate,
     ) -> Result<()> {
         let from = *batch_start_date
             - chrono::Duration::try_days(DEFAULT_STARTING_HABIT_ROWS as i64).unwrap();
-        let new_rows = datafile.get_rows(&from, batch_start_date)?;
-
-        let mut date = *batch_start_date;
-        for row in new_rows {
-            self.habit_rows.push((
-                date,
-                row.map(|cat_ids| table_utils::decode_habit_vector(&self.header, &cat_ids)),
-            ));
-            date -= chrono::Duration::try_days(1).unwrap();
+        // Rows are ordered from the latest to the earliest date
+        for record in datafile
+            .get_days(&from, batch_start_date)?
+            .into_iter()
+            .rev()
+        {
+            let cells = record.entries.as_ref().map(|_| {
+                self.header
+                    .iter()
+                    .map(|category| {
+                        let entry = record.get_entry(category.id);
+                        HabitCell {
+                            status: entry.map(|entry| entry.status),
+                            value: entry.and_then(|entry| entry.value),
+                        }
+                    })
+                    .collect()
+            });
+            self.habit_rows.push(HabitRow {
+                date: record.date,
+                cells,
+                excused: self
+                    .header
+                    .iter()
+                    .map(|category| record.is_excused(category.id))
+                    .collect(),
+            });
         }
         Ok(())
     }
 
-    fn ensure_habit_row_index(&mut self, datafile: &DiaryDataSqlite, index: usize) -> Result<()> {
+    fn ensure_habit_row_index(&mut self, datafile: &impl DiaryStore, index: usize) -> Result<()> {
         while index >= self.habit_rows.len() {
             self.load_habit_row_batch(
                 datafile,
@@ -131,15 +307,7 @@ impl HabitDayListWidget {
     }
 
     pub fn render(&mut self, frame: &mut Frame, area: Rect) {
-        let widths: Vec<Constraint> = (0..self.header.len() + 1)
-            .map(|i| {
-                if i == 0 {
-                    Constraint::Max(12)
-                } else {
-                    Constraint::Max(3)
-                }
-            })
-            .collect();
+        let widths = table_utils::get_column_widths(&self.header, 12, 4);
 
         let rows = self.get_daily_habit_rows();
 
@@ -149,55 +317,146 @@ impl HabitDayListWidget {
                 Block::bordered()
                     .title(self.get_footer())
                     .title_position(block::Position::Bottom)
-                    .title_top("Daily habit data"),
+                    .title_top(if self.read_only {
+                        "Daily habit data (read-only)"
+                    } else {
+                        "Daily habit data"
+                    })
+                    .title_top(self.get_selected_category_info().right_aligned()),
             );
         frame.render_stateful_widget(table, area, &mut self.habit_table_state);
     }
 
+    fn update_streak_info(&mut self, datafile: &impl DiaryStore) -> Result<()> {
+        let (Some(category), Some(date)) =
+            (self.header.get(self.edit_col_idx), self.get_selected_date())
+        else {
+            self.streak_info = String::new();
+            return Ok(());
+        };
+        self.streak_info = match category.polarity {
+            Polarity::Build => format!("streak: {} days", datafile.get_streak(category.id, &date)?),
+            Polarity::Avoid => match datafile.get_days_since_last_occurrence(category.id, &date)? {
+                Some(days) => format!("{} days since last occurrence", days),
+                None => String::from("never occurred"),
+            },
+        };
+        Ok(())
+    }
+
+    fn get_selected_category_info(&self) -> Line<'static> {
+        let Some(category) = self.header.get(self.edit_col_idx) else {
+            return Line::default();
+        };
+        let mut info = vec![];
+        if let Some(ref symbol) = category.details.symbol {
+            info.push(format!("{} ", symbol));
+        }
+        info.push(
+            category
+                .details
+                .display_name
+                .clone()
+                .unwrap_or_else(|| category.name.clone()),
+        );
+        if let Some(ref description) = category.details.description {
+            info.push(format!(": {}", description));
+        }
+        info.push(format!(" ({})", self.streak_info));
+        let line = Line::from(info.concat());
+        if let Some(color) = table_utils::get_category_color(category) {
+            line.fg(color)
+        } else {
+            line
+        }
+    }
+
     fn get_footer(&self) -> String {
-        format!(
-            "Step: <↑><↓> Stride ({}): <PgUp><PgDown> Toggle: <SPACE> Change column: <←><→> Exit: <Q>",
-            &self.scale
-        )
+        if self
+            .value_input
+            .as_deref()
+            .is_some_and(|input| parse_value(input).is_none())
+        {
+            String::from("Invalid value, enter a number: <0-9><.> Cancel: <ESC>")
+        } else if self.is_editing_value() {
+            String::from("Enter value: <0-9><.> Confirm: <ENTER> Cancel: <ESC>")
+        } else if self.read_only {
+            format!(
+                "Step: <↑><↓> Stride ({}): <PgUp><PgDown> Select range: <SHIFT+↑><SHIFT+↓> Change column: <←><→> Exit: <Q>",
+                &self.scale
+            )
+        } else {
+            format!(
+                "Step: <↑><↓> Stride ({}): <PgUp><PgDown> Select range: <SHIFT+↑><SHIFT+↓> Done/partial/skipped: <SPACE> Value: <e> Excuse day/habit: <x>/<X> Change column: <←><→> Exit: <Q>",
+                &self.scale
+            )
+        }
     }
 
     fn get_daily_habit_rows<'a>(&self) -> Vec<Row<'a>> {
         let categories = &self.header;
         let mut rows = vec![];
         for (row_idx, data_row) in self.habit_rows.iter().enumerate() {
-            let mut cells = vec![Cell::new(data_row.0.to_string())];
-            let habit_vector = data_row.1.as_ref();
+            let mut cells = vec![Cell::new(data_row.date.to_string())];
+            let habit_vector = data_row.cells.as_ref();
             if let Some(habit_vector) = habit_vector {
-                for (col_idx, val) in habit_vector.iter().enumerate() {
-                    let span = if *val {
-                        Span::from("✓")
-                    } else {
-                        Span::from(" ")
+                for (col_idx, cell) in habit_vector.iter().enumerate() {
+                    let category = &categories[col_idx];
+                    let span = match cell {
+                        HabitCell {
+                            status: Some(EntryStatus::Skipped),
+                            ..
+                        } => Span::from("»").fg(Color::Gray),
+                        HabitCell {
+                            status: Some(status),
+                            value: Some(value),
+                        } => {
+                            Span::from(format_value(*value)).fg(get_status_color(*status, category))
+                        }
+                        HabitCell {
+                            status: Some(EntryStatus::Partial),
+                            ..
+                        } => Span::from("◐").fg(Color::Yellow),
+                        HabitCell {
+                            status: Some(EntryStatus::Done),
+                            ..
+                        } => Span::from(String::from(table_utils::get_category_symbol(category)))
+                            .fg(get_status_color(EntryStatus::Done, category)),
+                        HabitCell { status: None, .. } => {
+                            self.get_unchecked_span(data_row, col_idx)
+                        }
                     };
                     if self.habit_table_state.selected() == Some(row_idx)
                         && self.edit_col_idx == col_idx
                     {
-                        cells.push(Cell::new(span.bg(Color::LightGreen)));
+                        cells.push(self.get_selected_cell_widget(span));
                     } else {
                         cells.push(Cell::new(span));
                     }
                 }
             } else if self.habit_table_state.selected() == Some(row_idx) {
                 for i in 0..categories.len() {
-                    let span = Span::from(" ");
+                    let span = self.get_unchecked_span(data_row, i);
                     if i == self.edit_col_idx {
-                        cells.push(Cell::new(span.bg(Color::LightGreen)));
+                        cells.push(self.get_selected_cell_widget(span));
                     } else {
                         cells.push(Cell::new(span));
                     }
                 }
             } else {
-                for _i in 0..categories.len() {
-                    cells.push(Cell::new("?"));
+                for excused in data_row.excused.iter() {
+                    if *excused {
+                        cells.push(Cell::new(get_excused_span()));
+                    } else {
+                        cells.push(Cell::new("?"));
+                    }
                 }
             }
             let row = Row::new(cells);
-            if self.habit_table_state.selected() == Some(row_idx) {
+            if self
+                .get_selected_row_range()
+                .is_some_and(|(first, last)| first <= row_idx && row_idx <= last)
+            {
                 rows.push(row.bg(Color::DarkGray));
             } else {
                 rows.push(row);
@@ -205,4 +464,126 @@ impl HabitDayListWidget {
         }
         rows
     }
+
+    /// Returns the span of an unchecked entry, marking the days the category is excused or not scheduled on.
+    fn get_unchecked_span<'a>(&self, row: &HabitRow, col_idx: usize) -> Span<'a> {
+        if row.excused[col_idx] {
+            get_excused_span()
+        } else if self.header[col_idx].schedule.is_scheduled(&row.date) {
+            Span::from(" ")
+        } else {
+            Span::from("·").fg(Color::DarkGray)
+        }
+    }
+
+    fn get_selected_cell_widget<'a>(&self, span: Span<'a>) -> Cell<'a> {
+        if let Some(ref input) = self.value_input {
+            let color = if parse_value(input).is_some() {
+                Color::LightYellow
+            } else {
+                Color::LightRed
+            };
+            Cell::new(Span::from(format!("{}_", input)).bg(color))
+        } else {
+            Cell::new(span.bg(Color::LightGreen))
+        }
+    }
+}
+
+/// Parses an entered value. An empty input removes the value, `None` means the input is invalid.
+fn parse_value(input: &str) -> Option<Option<f64>> {
+    if input.is_empty() {
+        return Some(None);
+    }
+    input.parse().ok().map(Some)
+}
+
+fn format_value(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{:.0}", value)
+    } else {
+        format!("{:.1}", value)
+    }
+}
+
+fn get_status_color(status: EntryStatus, category: &Category) -> Color {
+    match status {
+        EntryStatus::Done => table_utils::get_checked_color(category),
+        EntryStatus::Partial => Color::Yellow,
+        EntryStatus::Skipped => Color::Gray,
+    }
+}
+
+fn get_excused_span<'a>() -> Span<'a> {
+    Span::from("~").fg(Color::Cyan)
+}
+
+/// Returns for each category whether it is excused on the date.
+fn get_excused_vector(
+    header: &[Category],
+    excused_ranges: &[ExcusedRange],
+    date: &NaiveDate,
+) -> Vec<bool> {
+    header
+        .iter()
+        .map(|category| {
+            excused_ranges
+                .iter()
+                .any(|range| range.covers(date, category.id))
+        })
+        .collect()
+}
+
+#[test]
+fn test_habit_day_list_edits() {
+    use genee::datafile::DiaryDataMemory;
+    let read = CategoryId(2);
+    let mut data = DiaryDataMemory::new(&[String::from("GYM"), String::from("READ")]);
+    let today = NaiveDate::from_ymd_opt(2000, 5, 10).unwrap();
+    let mut widget = HabitDayListWidget::new(&data, today).unwrap();
+    assert_eq!(Some(today), widget.get_selected_date());
+
+    // Switching the value of a cell cycles through the statuses
+    widget
+        .update(&mut data, HabitDayListWidgetInput::NavigateColumn(1))
+        .unwrap();
+    widget
+        .update(&mut data, HabitDayListWidgetInput::SwitchValue)
+        .unwrap();
+    assert_eq!(Some(vec![read]), data.get_row(&today).unwrap());
+    widget
+        .update(&mut data, HabitDayListWidgetInput::SwitchValue)
+        .unwrap();
+    assert_eq!(
+        vec![(today, read, EntryStatus::Partial)],
+        data.get_statuses(&today, &today).unwrap()
+    );
+    assert_eq!("streak: 1 days", widget.streak_info);
+
+    // Values are recorded for the selected cell
+    widget
+        .update(&mut data, HabitDayListWidgetInput::BeginValueEdit)
+        .unwrap();
+    for c in "2.5".chars() {
+        widget
+            .update(&mut data, HabitDayListWidgetInput::EditValue(c))
+            .unwrap();
+    }
+    widget
+        .update(&mut data, HabitDayListWidgetInput::ConfirmValueEdit)
+        .unwrap();
+    assert_eq!(Some(2.5), data.get_value(&today, read).unwrap());
+
+    // The selected day is excused for the selected category
+    widget
+        .update(&mut data, HabitDayListWidgetInput::SwitchCategoryExcused)
+        .unwrap();
+    assert_eq!(
+        vec![ExcusedRange {
+            category_id: Some(read),
+            from: today,
+            until: today,
+        }],
+        data.get_excused_ranges().unwrap()
+    );
 }
@@ -1,12 +1,16 @@
 use crate::{
-    CliOptions,
-    datafile::{self, DiaryDataSqlite},
+    CliOptions, PeriodAlignment,
+    datafile::{
+        self, Category, CategoryId, CategoryStats, DiaryStore, Polarity, Schedule, Target,
+        TargetKind,
+    },
 };
 use anyhow::Result;
-use chrono::NaiveDate;
+use chrono::{NaiveDate, Weekday};
 use ratatui::{prelude::*, style::Color, widgets::*};
+use std::{collections::HashMap, fmt::Display};
 
-use super::Scale;
+use super::{Scale, table_utils};
 
 const COLORS: [Color; 6] = [
     Color::LightCyan,
@@ -21,13 +25,52 @@ fn get_color(idx: usize) -> Color {
     COLORS[idx % COLORS.len()]
 }
 
+#[derive(Clone, Copy, PartialEq)]
+enum Statistic {
+    Count,
+    Sum,
+    Average,
+}
+
+impl Statistic {
+    fn next(&self) -> Statistic {
+        match self {
+            Statistic::Count => Statistic::Sum,
+            Statistic::Sum => Statistic::Average,
+            Statistic::Average => Statistic::Count,
+        }
+    }
+
+    fn evaluate(&self, stats: &CategoryStats) -> f64 {
+        match self {
+            Statistic::Count => stats.weighted_count(),
+            Statistic::Sum => stats.sum,
+            Statistic::Average => stats.average().unwrap_or_default(),
+        }
+    }
+}
+
+impl Display for Statistic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Statistic::Count => f.write_str("day count"),
+            Statistic::Sum => f.write_str("sum"),
+            Statistic::Average => f.write_str("average"),
+        }
+    }
+}
+
 pub struct HabitFrequencyTableWidget {
-    header: Vec<(String, usize)>,
+    header: Vec<Category>,
+    targets: HashMap<CategoryId, Target>,
     begin_date: NaiveDate,
     scale: Scale,
+    alignment: PeriodAlignment,
+    week_start: Weekday,
     iters: usize,
+    statistic: Statistic,
     date_ranges: Vec<(NaiveDate, NaiveDate)>,
-    data_counts: Vec<Vec<usize>>,
+    data_counts: Vec<Vec<CategoryStats>>,
 }
 
 pub enum HabitFrequencyTableWidgetInput {
@@ -36,12 +79,14 @@ pub enum HabitFrequencyTableWidgetInput {
     LargerScale,
     FewerPeriods,
     MorePeriods,
+    NextStatistic,
+    SwitchAlignment,
     DataChanged,
 }
 
 impl HabitFrequencyTableWidget {
     pub fn new(
-        datafile: &DiaryDataSqlite,
+        datafile: &impl DiaryStore,
         begin_date: NaiveDate,
         opts: &CliOptions,
         scale: Scale,
@@ -49,8 +94,12 @@ impl HabitFrequencyTableWidget {
         let header = datafile.get_header()?;
         let mut result = HabitFrequencyTableWidget {
             header,
+            targets: datafile.get_targets()?,
             scale,
+            alignment: opts.period_alignment.unwrap_or_default(),
+            week_start: opts.week_start.unwrap_or(Weekday::Mon),
             iters: opts.past_periods.unwrap(),
+            statistic: Statistic::Count,
             begin_date,
             date_ranges: vec![],
             data_counts: vec![],
@@ -62,31 +111,29 @@ impl HabitFrequencyTableWidget {
     pub fn render(&self, frame: &mut Frame, area: Rect) {
         let inner_area = area.inner(Margin::new(1, 1));
         frame.render_widget(
-            Block::bordered()
-                .title_top(self.title())
-                .title_bottom("Change scale: <Ctrl> + <←><→> Change periods: <a><s>"),
+            Block::bordered().title_top(self.title()).title_bottom(
+                "Change scale: <Ctrl> + <←><→> Change periods: <a><s> Statistic: <v> Calendar: <c>",
+            ),
             area,
         );
 
-        const DATE_RANGE_CHAR_COUNT: u16 = 24; // "2024-01-29 - 2024-02-27 "
-        let date_range_num_chars = self.date_ranges.len() as u16 * DATE_RANGE_CHAR_COUNT;
-        let date_range_lines = date_range_num_chars.div_ceil(inner_area.width);
+        let date_range_labels: Vec<String> = self
+            .date_ranges
+            .iter()
+            .map(|range| format!("{} ", self.get_range_label(range)))
+            .collect();
+        let date_range_num_chars: usize = date_range_labels.iter().map(String::len).sum();
+        let date_range_lines = (date_range_num_chars as u16).div_ceil(inner_area.width);
 
         let inner_chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([Constraint::Max(date_range_lines), Constraint::Min(0)])
             .split(inner_area);
 
-        let date_list_text: Vec<Span> = self
-            .date_ranges
-            .iter()
+        let date_list_text: Vec<Span> = date_range_labels
+            .into_iter()
             .enumerate()
-            .map(|(idx, (from, to))| {
-                Span::styled(
-                    format!("{} - {} ", to, from),
-                    Style::default().fg(get_color(idx)),
-                )
-            })
+            .map(|(idx, label)| Span::styled(label, Style::default().fg(get_color(idx))))
             .collect();
         let date_list_text = Line::from(date_list_text);
         frame.render_widget(
@@ -101,21 +148,56 @@ impl HabitFrequencyTableWidget {
             .bar_gap(0)
             .bar_width(1)
             .group_gap(1)
-            .max(self.scale.value() as u64);
-        for (idx, (name, _id)) in self.header.iter().enumerate() {
+            .max(self.get_max_value());
+        for (idx, category) in self.header.iter().enumerate() {
+            let target = self
+                .targets
+                .get(&category.id)
+                .filter(|_| self.statistic == Statistic::Count);
+            let label = get_label(category);
+            // Categories with a schedule show the number of days they were scheduled on
+            let is_scheduled = category.schedule != Schedule::default();
             let bars: Vec<Bar> = self
                 .data_counts
                 .iter()
                 .enumerate()
                 .map(|(bar_idx, count_values)| {
-                    let label = if bar_idx == 0 { name.as_str() } else { "" };
-                    let count_value = count_values[idx];
-                    let count_text = format!("{:2}", count_value);
-                    Bar::default()
-                        .value(count_value as u64)
-                        .text_value(count_text)
-                        .label(Line::from(label))
-                        .style(Style::default().fg(get_color(bar_idx)))
+                    let label = if bar_idx == 0 {
+                        label.clone()
+                    } else {
+                        Line::default()
+                    };
+                    let value = self.statistic.evaluate(&count_values[idx]);
+                    let bar = Bar::default()
+                        .value(value.round() as u64)
+                        .label(label)
+                        .style(Style::default().fg(get_color(bar_idx)));
+                    if let Some(target) = target {
+                        let count = count_values[idx].weighted_count();
+                        // The target is scaled to the scheduled days that were not excused
+                        let scheduled_days = count_values[idx].scheduled_days;
+                        let target_color =
+                            if target.is_met(count, &category.schedule, scheduled_days) {
+                                Color::Green
+                            } else {
+                                Color::Red
+                            };
+                        bar.text_value(format!(
+                            "{:2}/{}",
+                            count,
+                            format_target_count(
+                                target,
+                                target.scaled_count(&category.schedule, scheduled_days)
+                            )
+                        ))
+                        .value_style(Style::default().fg(Color::Black).bg(target_color))
+                    } else if self.statistic == Statistic::Count && is_scheduled {
+                        bar.text_value(format!("{:2}/{}", value, count_values[idx].scheduled_days))
+                    } else if self.statistic == Statistic::Count {
+                        bar.text_value(format!("{:2}", value))
+                    } else {
+                        bar.text_value(format!("{:.1}", value))
+                    }
                 })
                 .collect();
             let bar_group = BarGroup::default().bars(&bars);
@@ -126,7 +208,7 @@ impl HabitFrequencyTableWidget {
 
     pub fn update(
         &mut self,
-        datafile: &DiaryDataSqlite,
+        datafile: &impl DiaryStore,
         input: HabitFrequencyTableWidgetInput,
     ) -> Result<()> {
         match input {
@@ -152,6 +234,16 @@ impl HabitFrequencyTableWidget {
                 self.iters = usize::max(1usize, self.iters + 1);
                 self.recalculate(datafile)?;
             }
+            HabitFrequencyTableWidgetInput::NextStatistic => {
+                self.statistic = self.statistic.next();
+            }
+            HabitFrequencyTableWidgetInput::SwitchAlignment => {
+                self.alignment = match self.alignment {
+                    PeriodAlignment::Rolling => PeriodAlignment::Calendar,
+                    PeriodAlignment::Calendar => PeriodAlignment::Rolling,
+                };
+                self.recalculate(datafile)?;
+            }
             HabitFrequencyTableWidgetInput::DataChanged => {
                 self.recalculate(datafile)?;
             }
@@ -168,19 +260,93 @@ impl HabitFrequencyTableWidget {
 
     pub fn update_opts(&self, opts: &mut CliOptions) {
         opts.past_periods = Some(self.iters);
+        opts.period_alignment = Some(self.alignment);
     }
 
-    fn recalculate(&mut self, datafile: &DiaryDataSqlite) -> Result<()> {
-        self.date_ranges =
-            datafile::get_date_ranges(&self.begin_date, self.scale.value(), self.iters);
+    fn recalculate(&mut self, datafile: &impl DiaryStore) -> Result<()> {
+        self.date_ranges = match self.alignment {
+            PeriodAlignment::Rolling => {
+                datafile::get_date_ranges(&self.begin_date, self.scale.value(), self.iters)
+            }
+            PeriodAlignment::Calendar => datafile::get_calendar_date_ranges(
+                &self.begin_date,
+                self.scale.calendar_period(),
+                self.week_start,
+                self.iters,
+            ),
+        };
         self.data_counts = datafile.calculate_data_counts_per_iter(&self.date_ranges)?;
         Ok(())
     }
 
+    fn get_max_value(&self) -> u64 {
+        if self.statistic == Statistic::Count {
+            return self
+                .date_ranges
+                .iter()
+                .map(get_range_days)
+                .max()
+                .unwrap_or(1) as u64;
+        }
+        self.data_counts
+            .iter()
+            .flatten()
+            .map(|stats| self.statistic.evaluate(stats).round() as u64)
+            .max()
+            .unwrap_or_default()
+            .max(1)
+    }
+
     fn title(&self) -> String {
+        let alignment = match self.alignment {
+            PeriodAlignment::Rolling => "rolling",
+            PeriodAlignment::Calendar => "calendar",
+        };
         format!(
-            "Habit histogram: {} {} periods until {}",
-            self.iters, self.scale, self.begin_date
+            "Habit histogram ({}): {} {} {} periods until {}",
+            self.statistic, self.iters, alignment, self.scale, self.begin_date
         )
     }
+
+    /// Returns the label of a period, e.g. "2024-01-29 - 2024-02-27" or "2024-02".
+    fn get_range_label(&self, (until, from): &(NaiveDate, NaiveDate)) -> String {
+        match self.alignment {
+            PeriodAlignment::Rolling => format!("{} - {}", from, until),
+            PeriodAlignment::Calendar => self.scale.calendar_period().label(from),
+        }
+    }
+}
+
+/// Returns the number of days in a range given by its last and first day.
+fn get_range_days((until, from): &(NaiveDate, NaiveDate)) -> usize {
+    (*until - *from).num_days() as usize + 1
+}
+
+/// Formats a scaled target count with at most one decimal. It is rounded towards the side that
+/// fails the target, so that e.g. 3 occurrences are not shown as meeting a target of 3.4.
+fn format_target_count(target: &Target, count: f64) -> String {
+    // The tolerance keeps the whole counts from being rounded because of floating point errors
+    let tenths = match target.kind {
+        TargetKind::AtLeast => (count * 10.0 - 1e-9).ceil(),
+        TargetKind::AtMost => (count * 10.0 + 1e-9).floor(),
+    };
+    if tenths % 10.0 == 0.0 {
+        format!("{:.0}", tenths / 10.0)
+    } else {
+        format!("{:.1}", tenths / 10.0)
+    }
+}
+
+fn get_label(category: &Category) -> Line<'static> {
+    let mut label = match category.details.symbol {
+        Some(ref symbol) => format!("{} {}", symbol, category.name),
+        None => category.name.clone(),
+    };
+    if category.polarity == Polarity::Avoid {
+        label.push_str(" ↓");
+    }
+    match table_utils::get_category_color(category) {
+        Some(color) => Line::from(label).fg(color),
+        None => Line::from(label),
+    }
 }
@@ -1,28 +1,45 @@
+use genee::datafile::{Category, Polarity};
 use ratatui::{prelude::*, widgets::*};
+use std::str::FromStr;
 
-pub fn get_table_header<'a>(header: &[(String, usize)], first: &'a str) -> Row<'a> {
+pub fn get_table_header<'a>(header: &[Category], first: &'a str) -> Row<'a> {
     let mut cells = vec![Cell::new(first)];
-    for (name, _idx) in header {
-        cells.push(Cell::new(name.clone()));
+    for category in header {
+        let mut cell = Cell::new(category.name.clone());
+        if let Some(color) = get_category_color(category) {
+            cell = cell.fg(color);
+        }
+        cells.push(cell);
     }
     Row::new(cells).add_modifier(Modifier::BOLD)
 }
 
-pub fn decode_habit_vector(categories: &[(String, usize)], ids: &[usize]) -> Vec<bool> {
-    let mut v = vec![];
-    for (_, cat_id) in categories {
-        v.push(ids.contains(cat_id));
+pub fn get_category_color(category: &Category) -> Option<Color> {
+    category
+        .details
+        .color
+        .as_ref()
+        .and_then(|color| Color::from_str(color).ok())
+}
+
+pub fn get_category_symbol(category: &Category) -> &str {
+    category.details.symbol.as_deref().unwrap_or("✓")
+}
+
+/// Returns the colour of a checked entry, signalling success or failure according to the polarity.
+pub fn get_checked_color(category: &Category) -> Color {
+    match category.polarity {
+        Polarity::Build => Color::LightGreen,
+        Polarity::Avoid => Color::LightRed,
     }
-    v
 }
 
-pub fn encode_habit_vector(categories: &[(String, usize)], entries: &[bool]) -> Vec<usize> {
-    assert_eq!(categories.len(), entries.len());
-    let mut entry_ids = vec![];
-    for (val, (_name, cat_id)) in entries.iter().zip(categories.iter()) {
-        if *val {
-            entry_ids.push(*cat_id);
-        }
+pub fn get_column_widths(header: &[Category], first_width: u16, min_width: u16) -> Vec<Constraint> {
+    let mut widths = vec![Constraint::Max(first_width)];
+    for category in header {
+        widths.push(Constraint::Length(
+            (Span::from(category.name.as_str()).width() as u16).max(min_width),
+        ));
     }
-    entry_ids
+    widths
 }
@@ -1,5 +1,8 @@
 use super::table_utils;
-use crate::{CliOptions, datafile::DiaryDataSqlite};
+use crate::{
+    CliOptions,
+    datafile::{Category, CategoryId, DiaryStore, Polarity},
+};
 use anyhow::{Ok, Result};
 use chrono::NaiveDate;
 use ratatui::{prelude::*, widgets::*};
@@ -12,18 +15,23 @@ pub struct TopOccurrenceListWidget {
     range_from: NaiveDate,
     range_until: NaiveDate,
     count: usize,
-    header: Vec<(String, usize)>,
-    data: Vec<(Vec<usize>, usize)>,
+    header: Vec<Category>,
+    data: Vec<(Vec<CategoryId>, usize)>,
 }
 
 impl TopOccurrenceListWidget {
     pub fn new(
-        datafile: &DiaryDataSqlite,
+        datafile: &impl DiaryStore,
         range_from: NaiveDate,
         range_until: NaiveDate,
         opts: &CliOptions,
     ) -> Result<Self> {
-        let header = datafile.get_header()?;
+        // The habits to avoid are not part of the compositions
+        let header = datafile
+            .get_header()?
+            .into_iter()
+            .filter(|category| category.polarity == Polarity::Build)
+            .collect();
         let mut widget = TopOccurrenceListWidget {
             range_from,
             range_until,
@@ -36,21 +44,14 @@ impl TopOccurrenceListWidget {
     }
 
     pub fn render(&mut self, frame: &mut Frame, area: Rect) {
-        let widths: Vec<Constraint> = (0..self.header.len() + 1)
-            .map(|i| {
-                if i == 0 {
-                    Constraint::Max(5)
-                } else {
-                    Constraint::Max(3)
-                }
-            })
-            .collect();
+        let widths = table_utils::get_column_widths(&self.header, 5, 3);
         let mut rows = vec![table_utils::get_table_header(&self.header, "Count")];
         for (ids, count) in &self.data {
             let mut cells = vec![Cell::new(format!("{:5}", count))];
-            for habit_val in table_utils::decode_habit_vector(&self.header, ids) {
-                cells.push(if habit_val {
-                    Cell::from("✓")
+            for category in &self.header {
+                cells.push(if ids.contains(&category.id) {
+                    Cell::from(String::from(table_utils::get_category_symbol(category)))
+                        .fg(table_utils::get_checked_color(category))
                 } else {
                     Cell::from(" ")
                 });
@@ -67,7 +68,7 @@ impl TopOccurrenceListWidget {
 
     pub fn update(
         &mut self,
-        datafile: &DiaryDataSqlite,
+        datafile: &impl DiaryStore,
         input: TopOccurrenceListWidgetInput,
     ) -> Result<()> {
         match input {
@@ -84,7 +85,7 @@ impl TopOccurrenceListWidget {
         opts.list_most_frequent_days = Some(self.count);
     }
 
-    fn update_data(&mut self, datafile: &DiaryDataSqlite) -> Result<()> {
+    fn update_data(&mut self, datafile: &impl DiaryStore) -> Result<()> {
         self.data = datafile.get_most_frequent_daily_data(
             &Some(self.range_from),
             &self.range_until,